@@ -1,16 +1,19 @@
 use crate::*;
 use near_sdk::{ext_contract, Gas};
+use near_sdk::json_types::U64;
 
 const GAS_FOR_NFT_APPROVE: Gas = Gas(10_000_000_000_000);
 const NO_DEPOSIT: Balance = 0;
 
 pub trait NonFungibleTokenCore {
     //approve an account ID to transfer a token on your behalf
+    //the approval can optionally expire (Unix epoch in nanoseconds)
     fn nft_approve(
         &mut self,
         token_id: TokenId,
         account_id: AccountId,
-        msg: Option<String>
+        msg: Option<String>,
+        expires_at: Option<U64>,
     );
 
     //check if the passed in account has access to approve the token ID
@@ -49,7 +52,8 @@ impl NonFungibleTokenCore for Contract {
         &mut self,
         token_id: TokenId,
         account_id: AccountId,
-        msg: Option<String>
+        msg: Option<String>,
+        expires_at: Option<U64>,
     ) {
         /*
             assert at least one yocto for security reasons - this will cause a redirect to the NEAR wallet.
@@ -67,25 +71,44 @@ impl NonFungibleTokenCore for Contract {
             "Predecessor must be the token owner."
         );
 
+        if let Some(expires_at) = expires_at {
+            assert!(
+                env::block_timestamp() < expires_at.0,
+                "An approval can't expire in the past"
+            );
+        }
+
         //get the next approval ID if we need a new approval
         let approval_id: u64 = token.next_approval_id;
 
         //check if the account has been approved already for this token
-        let is_new_approval = token
+        let previous_approval_id = token
             .approved_account_ids
             //insert returns none if the key was not present.  
-            .insert(account_id.clone(), approval_id)
-            //if the key was not present, .is_none() will return true so it is a new approval.
-            .is_none();
+            .insert(account_id.clone(), approval_id);
+        //if the key was not present, .is_none() will return true so it is a new approval.
+        let is_new_approval = previous_approval_id.is_none();
 
         //if it was a new approval, we need to calculate how much storage is being used to add the account.
-        let storage_used = if is_new_approval {
+        let mut storage_used = if is_new_approval {
             bytes_for_approved_account_id(&account_id)
         //if it was not a new approval, we used no storage.
         } else {
             0
         };
 
+        //EXPIRY
+        //the expiry is kept apart from the token, under the token and approval IDs.
+        //Re-approving an account replaces the expiry of its previous approval.
+        let initial_storage_usage = env::storage_usage();
+        if let Some(previous_approval_id) = previous_approval_id {
+            self.approval_expiries.remove(&(token_id.clone(), previous_approval_id));
+        }
+        if let Some(expires_at) = expires_at {
+            self.approval_expiries.insert(&(token_id.clone(), approval_id), &expires_at.0);
+        }
+        storage_used += env::storage_usage().saturating_sub(initial_storage_usage);
+
         /*
         increment the token's next approval ID by 1
         we're incrementing the next_approval_id regardless of whether the account_id we're giving
@@ -99,6 +122,25 @@ impl NonFungibleTokenCore for Contract {
         //refund any excess storage attached by the user. If the user didn't attach enough, panic. 
         refund_deposit(storage_used);
 
+        //LOG EVENTS
+        let nft_approve_log: EventLog = EventLog {
+            //standard name ("nearoracle")
+            standard: ORACLE_STANDARD_NAME.to_string(),
+            //version of the standard ("1.0.0")
+            version: ORACLE_EVENT_VERSION.to_string(),
+            //the data related with the event stored in a vector
+            event: EventLogVariant::NftApprove(vec![NftApproveLog {
+                owner_id: token.owner_id.to_string(),
+                token_id: token_id.clone(),
+                account_id: account_id.to_string(),
+                approval_id,
+                expires_at,
+            }]),
+        };
+
+        //log the serialized json
+        env::log_str(&nft_approve_log.to_string());
+
         //if some message was passed into the function, we initiate a cross contract call on the
         //account we're giving access to. 
         if let Some(msg) = msg {
@@ -125,8 +167,8 @@ impl NonFungibleTokenCore for Contract {
         //get the approval number for the passed in approval_id
         let approval = token.approved_account_ids.get(&approved_account_id);
 
-        //if it found an approval ID for that account
-        if let Some(approval) =  approval {
+        //if it found an approval ID for that account, and the approval hasn't expired
+        if let Some(approval) = approval.filter(|approval| !self.approval_expired(&token_id, **approval)) {
             //if a sepcific approval id was passed into the function
             if let Some(approval_id) =  approval_id {
                 //return if the approval ID passed in matches the actual approval ID for the account
@@ -135,9 +177,10 @@ impl NonFungibleTokenCore for Contract {
             } else {
                 true
             }
-        //if there was no approval ID found for the account ID, we simply return false
+        //if there was no approval ID found for the account ID, the account can still
+        //transfer the token as an operator of its owner (operators carry no approval ID)
         } else {
-            false
+            approval_id.is_none() && self.internal_is_operator(&token.owner_id, &approved_account_id)
         }
     }
 
@@ -157,16 +200,36 @@ impl NonFungibleTokenCore for Contract {
         assert_eq!(&predecessor_account_id, &token.owner_id);
         
         //if the account ID was in the token's approval, we remove it and the if statement logic executes
-        if token 
+        if let Some(approval_id) = token 
             .approved_account_ids
             .remove(&account_id)
-            .is_some()
             {
-                //refund the funds released by removing the approved_account_id to the caller of the function
-                refund_approved_account_ids_iter(predecessor_account_id, [account_id].iter());
+                //refund the funds released by removing the approved_account_id (and its expiry) to the caller of the function
+                self.internal_refund_approvals(
+                    predecessor_account_id.clone(),
+                    &token_id,
+                    &HashMap::from([(account_id.clone(), approval_id)]),
+                );
 
                 //insert the token back into the token_by_id collection with the account_id removed from the approval list
                 self.token_by_id.insert(&token_id, &token);
+
+                //LOG EVENTS
+                let nft_revoke_log: EventLog = EventLog {
+                    //standard name ("nearoracle")
+                    standard: ORACLE_STANDARD_NAME.to_string(),
+                    //version of the standard ("1.0.0")
+                    version: ORACLE_EVENT_VERSION.to_string(),
+                    //the data related with the event stored in a vector
+                    event: EventLogVariant::NftRevoke(vec![NftRevokeLog {
+                        owner_id: predecessor_account_id.to_string(),
+                        token_id,
+                        account_ids: vec![account_id.to_string()],
+                    }]),
+                };
+
+                //log the serialized json
+                env::log_str(&nft_revoke_log.to_string());
             }
     }
 
@@ -186,13 +249,89 @@ impl NonFungibleTokenCore for Contract {
 
         //only revoke if the approved account IDs for the token is not empty
         if !token.approved_account_ids.is_empty() {
-            //refund the approved account IDs to the caller of the function
-            refund_approved_account_ids(predecessor_account_id, &token.approved_account_ids);
+            //refund the approved account IDs (and their expiries) to the caller of the function
+            self.internal_refund_approvals(predecessor_account_id.clone(), &token_id, &token.approved_account_ids);
+            let account_ids = token.approved_account_ids.keys().map(|account_id| account_id.to_string()).collect();
             //clear the approved account IDs
             token.approved_account_ids.clear();
 
             //insert the token back into the token_by_id collection with the account_id cleared
             self.token_by_id.insert(&token_id, &token);
+
+            //LOG EVENTS
+            let nft_revoke_log: EventLog = EventLog {
+                //standard name ("nearoracle")
+                standard: ORACLE_STANDARD_NAME.to_string(),
+                //version of the standard ("1.0.0")
+                version: ORACLE_EVENT_VERSION.to_string(),
+                //the data related with the event stored in a vector
+                event: EventLogVariant::NftRevoke(vec![NftRevokeLog {
+                    owner_id: predecessor_account_id.to_string(),
+                    token_id,
+                    account_ids,
+                }]),
+            };
+
+            //log the serialized json
+            env::log_str(&nft_revoke_log.to_string());
+        }
+    }
+}
+
+//an approval of a token, as returned by the nft_approvals() view
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ApprovalInfo {
+    pub account_id: AccountId,
+    pub approval_id: u64,
+    //when the approval expires, Unix epoch in nanoseconds, None means it never expires
+    pub expires_at: Option<U64>,
+}
+
+#[near_bindgen]
+impl Contract {
+    //Query the accounts approved to transfer the given token, with their approval IDs and expiries
+    pub fn nft_approvals(&self, token_id: TokenId) -> Vec<ApprovalInfo> {
+        let token = self.token_by_id.get(&token_id).expect("No token");
+        token
+            .approved_account_ids
+            .into_iter()
+            .map(|(account_id, approval_id)| ApprovalInfo {
+                account_id,
+                approval_id,
+                expires_at: self.approval_expiries.get(&(token_id.clone(), approval_id)).map(U64),
+            })
+            .collect()
+    }
+}
+
+impl Contract {
+    //whether the given approval of a token has an expiry that has passed
+    pub(crate) fn approval_expired(&self, token_id: &TokenId, approval_id: u64) -> bool {
+        self.approval_expiries
+            .get(&(token_id.clone(), approval_id))
+            .map_or(false, |expires_at| expires_at <= env::block_timestamp())
+    }
+
+    //remove the expiries of the given approvals of a token, and refund the storage released
+    //by the approved account IDs and by their expiries to the given account
+    pub(crate) fn internal_refund_approvals(
+        &mut self,
+        account_id: AccountId,
+        token_id: &TokenId,
+        approved_account_ids: &HashMap<AccountId, u64>,
+    ) -> Promise {
+        let initial_storage_usage = env::storage_usage();
+        for approval_id in approved_account_ids.values() {
+            self.approval_expiries.remove(&(token_id.clone(), *approval_id));
         }
+        let expiries_released = initial_storage_usage - env::storage_usage();
+
+        let storage_released: u64 = approved_account_ids
+            .keys()
+            .map(bytes_for_approved_account_id)
+            .sum::<u64>()
+            + expiries_released;
+        Promise::new(account_id).transfer(Balance::from(storage_released) * env::storage_byte_cost())
     }
-}
\ No newline at end of file
+}
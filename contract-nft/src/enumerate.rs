@@ -60,12 +60,14 @@ impl Contract {
     Query for all the tokens for an owner. More specifically, 
     query for a paginated list of NFTs owned by a given account ID.
     This is the function that displays your NFTs among the collectibles in your NEAR wallet.
+    Pass 'exclude_expired' to leave out the tokens that are not within their validity window.
      */
     pub fn nft_tokens_for_owner(
         &self,
         account_id: &AccountId,
         from_index: Option<U128>,
         limit: Option<u64>,
+        exclude_expired: Option<bool>,
     ) -> Vec<JsonToken> {
         //get the set of tokens for a given owner
         let tokens_for_owner_set = self.tokens_per_owner.get(&account_id);
@@ -81,6 +83,19 @@ impl Contract {
         // where to start pagination
         // - if we have a from_index, we'll use that - otherwise return ALL tokens
         let start = u128::from(from_index.unwrap_or(U128(0)));
+        let limit = limit.unwrap_or(tokens.len()) as usize;
+
+        //if requested, filter out the tokens outside of their validity window before paginating
+        if exclude_expired.unwrap_or(false) {
+            let now = env::block_timestamp();
+            return tokens
+                .iter()
+                .filter_map(|token_id| self.json_token(token_id))
+                .filter(|token| token_is_valid(&token.metadata, now))
+                .skip(start as usize)
+                .take(limit)
+                .collect();
+        }
 
         //iterate through the keys vector
         tokens
@@ -88,9 +103,9 @@ impl Contract {
             //skip to the index we specified in the start variable
             .skip(start as usize)
             //take the first "limit" elements in the vector. If we didn't specify a limit, return until the last element
-            .take(limit.unwrap_or(tokens.len()) as usize)
+            .take(limit)
             //we'll map the token IDs which are strings into Json Tokens
-            .map(|token_id| self.json_token(token_id.clone()).unwrap())
+            .filter_map(|token_id| self.json_token(token_id))
             //since we turned the keys into an iterator, we need to turn it back into a vector to return
             .collect()
     }
@@ -1,8 +1,12 @@
 use std::fmt;
 use near_sdk::serde::{Serialize, Deserialize};
+use near_sdk::json_types::U64;
+use crate::MintPolicy;
 
 /// Enum that represents the data type of the EventLog.
-/// The enum can either be an NftMint or an NftTransfer.
+/// The enum can either be one of the NEP-171 events (NftMint, NftTransfer)
+/// or one of the contract-specific events (MintPolicyUpdate, NftApprove, NftRevoke,
+/// NftOperatorApprove, NftOperatorRevoke, NftMintFailure).
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(tag = "event", content = "data")]
 #[serde(rename_all = "snake_case")]
@@ -12,6 +16,12 @@ use near_sdk::serde::{Serialize, Deserialize};
 pub enum EventLogVariant {
     NftMint(Vec<NftMintLog>),
     NftTransfer(Vec<NftTransferLog>),
+    MintPolicyUpdate(Vec<MintPolicyUpdateLog>),
+    NftApprove(Vec<NftApproveLog>),
+    NftRevoke(Vec<NftRevokeLog>),
+    NftOperatorApprove(Vec<NftOperatorLog>),
+    NftOperatorRevoke(Vec<NftOperatorLog>),
+    NftMintFailure(Vec<NftMintFailureLog>),
 }
 
 /*
@@ -77,4 +87,75 @@ pub struct NftTransferLog {
     pub token_ids: Vec<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub memo: Option<String>,
-}
\ No newline at end of file
+}
+
+/// An event log to capture a change of the mint policy
+///
+/// Arguments
+/// * `updated_by`: "owner.near"
+/// * `policy`: the mint policy in force from now on
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MintPolicyUpdateLog {
+    pub updated_by: String,
+    pub policy: MintPolicy,
+}
+
+/// An event log to capture an account being approved to transfer a token
+///
+/// Arguments
+/// * `owner_id`: "owner.near"
+/// * `token_id`: "12345abc"
+/// * `account_id`: "market.near"
+/// * `approval_id`: 3
+/// * `expires_at`: optional expiry of the approval, Unix epoch in nanoseconds
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftApproveLog {
+    pub owner_id: String,
+    pub token_id: String,
+    pub account_id: String,
+    pub approval_id: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<U64>,
+}
+
+/// An event log to capture approvals of a token being revoked
+///
+/// Arguments
+/// * `owner_id`: "owner.near"
+/// * `token_id`: "12345abc"
+/// * `account_ids`: ["market.near", "friend.near"]
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftRevokeLog {
+    pub owner_id: String,
+    pub token_id: String,
+    pub account_ids: Vec<String>,
+}
+
+/// An event log to capture an operator being approved (or revoked) to transfer all the tokens of an owner
+///
+/// Arguments
+/// * `owner_id`: "owner.near"
+/// * `operator_id`: "market.near"
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftOperatorLog {
+    pub owner_id: String,
+    pub operator_id: String,
+}
+
+/// An event log to capture a mint that was refused after its score was checked
+///
+/// Arguments
+/// * `owner_id`: "receiver.near"
+/// * `token_id`: "12345abc"
+/// * `reason`: why the token wasn't minted
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftMintFailureLog {
+    pub owner_id: String,
+    pub token_id: String,
+    pub reason: String,
+}
@@ -0,0 +1,123 @@
+use crate::*;
+use near_sdk::json_types::U64;
+
+/*
+Indexes that keep the checks run by nft_mint() in constant time, regardless of how many
+tokens the receiver holds:
+- last_minted_at: the last time a token was minted to a given account (for the cooldown)
+- tokens_by_media_hash: the first token minted with a given media content hash
+- media_hashes_per_owner: the (receiver, media content hash) pairs that were already minted
+ */
+
+//key of the media_hashes_per_owner set for a given receiver and media content hash
+pub(crate) fn hash_owner_media(account_id: &AccountId, media_hash: &[u8]) -> CryptoHash {
+    let mut preimage = account_id.as_bytes().to_vec();
+    preimage.extend_from_slice(media_hash);
+    let mut hash = CryptoHash::default();
+    hash.copy_from_slice(&env::sha256(&preimage));
+    hash
+}
+
+impl Contract {
+    //record a freshly minted token in the mint indexes
+    pub(crate) fn internal_index_mint(
+        &mut self,
+        owner_id: &AccountId,
+        token_id: &TokenId,
+        media_hash: &[u8],
+        minted_at: u64,
+    ) {
+        //keep the latest mint time of the owner
+        if self.last_minted_at.get(owner_id).map_or(true, |last| last < minted_at) {
+            self.last_minted_at.insert(owner_id, &minted_at);
+        }
+        //only the first token minted with a media content hash is recorded
+        if !self.tokens_by_media_hash.contains_key(&media_hash.to_vec()) {
+            self.tokens_by_media_hash.insert(&media_hash.to_vec(), token_id);
+        }
+        self.media_hashes_per_owner.insert(&hash_owner_media(owner_id, media_hash));
+    }
+
+    //remove a token that was just minted from the mint indexes,
+    //given the last mint time of its owner before that mint
+    pub(crate) fn internal_unindex_mint(
+        &mut self,
+        owner_id: &AccountId,
+        token_id: &TokenId,
+        media_hash: &[u8],
+        last_minted_at: Option<u64>,
+    ) {
+        match last_minted_at {
+            Some(last_minted_at) => self.last_minted_at.insert(owner_id, &last_minted_at),
+            None => self.last_minted_at.remove(owner_id),
+        };
+        if self.tokens_by_media_hash.get(&media_hash.to_vec()).as_ref() == Some(token_id) {
+            self.tokens_by_media_hash.remove(&media_hash.to_vec());
+        }
+        self.media_hashes_per_owner.remove(&hash_owner_media(owner_id, media_hash));
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    //Query the last time (in nanoseconds) a token was minted to the given account
+    pub fn nft_last_minted_at(&self, account_id: AccountId) -> Option<U64> {
+        self.last_minted_at.get(&account_id).map(U64)
+    }
+
+    //Query the first token minted with the given media content hash
+    pub fn nft_token_by_media_hash(&self, media_hash: Base64VecU8) -> Option<TokenId> {
+        self.tokens_by_media_hash.get(&media_hash.0)
+    }
+
+    //Sets whether the "can't mint the same NFT twice" rule applies to the whole contract
+    //rather than to each receiver.
+    //This method can be called only by the smart contract owner.
+    pub fn set_unique_media_contract_wide(&mut self, contract_wide: bool) {
+        //ensure the function was called by the smart contract owner, else panic
+        self.assert_called_by_owner();
+
+        self.unique_media_contract_wide = contract_wide;
+    }
+
+    /*
+    Record the tokens minted before the mint indexes existed, so that the
+    cooldown and duplicate checks also take them into account,
+    along with their typed score so that nft_verify_score() finds them.
+    Runs over a paginated range of tokens so it fits in the gas limit.
+    Run it after migrate() and migrate_token_timestamps(), so that mint times are in nanoseconds.
+    This method can be called only by the smart contract owner.
+     */
+    pub fn index_minted_tokens(&mut self, from_index: Option<U128>, limit: Option<u64>) {
+        //ensure the function was called by the smart contract owner, else panic
+        self.assert_called_by_owner();
+
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+        let token_ids: Vec<TokenId> = self
+            .token_metadata_by_id
+            .keys()
+            .skip(start as usize)
+            .take(limit.unwrap_or(50) as usize)
+            .collect();
+
+        for token_id in token_ids {
+            let metadata = self.token_metadata_by_id.get(&token_id).unwrap();
+            let owner_id = self.token_by_id.get(&token_id).unwrap().owner_id;
+            //legacy tokens may lack the hash or the mint timestamp
+            let media_hash = metadata
+                .media_hash
+                .map(|hash| hash.0)
+                .unwrap_or_else(|| env::sha256(metadata.media.as_bytes()));
+            self.internal_index_mint(&owner_id, &token_id, &media_hash, metadata.issued_at.unwrap_or(0));
+            //legacy tokens only carry their score in 'extra'
+            if let Some(score) = score_from_extra(&metadata.extra) {
+                if !self.score_by_token.contains_key(&token_id) {
+                    self.score_by_token.insert(&token_id, &TokenScore {
+                        score,
+                        issued_at: U64(metadata.issued_at.unwrap_or(0)),
+                    });
+                }
+            }
+        }
+    }
+}
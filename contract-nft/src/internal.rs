@@ -27,29 +27,6 @@ pub (crate) fn bytes_for_approved_account_id(account_id: &AccountId) -> u64 {
     account_id.as_str().len() as u64 + 4 + size_of::<u64>() as u64
 }
 
-//refund the cost for storage taken up by the approved account IDs saved under a given account
-//and send the funds to the given account
-pub (crate) fn refund_approved_account_ids_iter<'a, I>(
-    account_id: AccountId,
-    //the approved account IDs must be passed in as an iterator "I"
-    approved_account_ids: I, 
-) -> Promise where I: Iterator<Item = &'a AccountId>,
-{
-    //get the storage total by going through and summing all the bytes for each approved account IDs
-    let storage_released: u64 = approved_account_ids.map(bytes_for_approved_account_id).sum();
-    //transfer into the account the storage that is released
-    Promise::new(account_id).transfer(Balance::from(storage_released) * env::storage_byte_cost())
-}
-
-//takes a map of approved account IDs and refund the storage cost to a given account
-pub (crate) fn refund_approved_account_ids(
-    account_id: AccountId,
-    approved_account_ids: &HashMap<AccountId, u64>,
-) -> Promise {
-    //call the function "refund_Approved_account_ids_iter" and pass the approved account IDs as keys
-    refund_approved_account_ids_iter(account_id, approved_account_ids.keys())
-}
-
 //used to generate a unique prefix in our storage collections (this is to avoid data collisions)
 pub(crate) fn hash_account_id(account_id: &AccountId) -> CryptoHash {
     //get the default hash
@@ -69,27 +46,65 @@ pub (crate) fn assert_at_least_one_yocto() {
 
 //refund the initial deposit based on the amount of storage that was used up
 pub(crate) fn refund_deposit(storage_used: u64) {
+    refund_deposit_after_payment(storage_used, 0)
+}
+
+//refund the initial deposit based on the amount of storage that was used up
+//and on the amount of NEAR that was paid out of it (e.g. a mint price)
+pub(crate) fn refund_deposit_after_payment(storage_used: u64, payment: Balance) {
+    refund_deposit_of(&env::predecessor_account_id(), env::attached_deposit(), storage_used, payment)
+}
+
+//refund the given account the part of its deposit that wasn't used up by
+//the storage and by the amount of NEAR that was paid out of it (e.g. a mint price).
+//Used by callbacks, which get the deposit of the original call as an argument
+pub(crate) fn refund_deposit_of(account_id: &AccountId, attached_deposit: Balance, storage_used: u64, payment: Balance) {
     //get how much it would cost to store the information
-    let required_cost = env::storage_byte_cost() * Balance::from(storage_used);
-    //get the attached deposit
-    let attached_deposit = env::attached_deposit();
+    let storage_cost = env::storage_byte_cost() * Balance::from(storage_used);
+    let required_cost = storage_cost + payment;
 
     //make sure that the attached deposit is greater than or equal to the required cost
-    assert!(
-        required_cost <= attached_deposit,
-        "Must attach {} yoctoNEAR to cover storage",
-        required_cost,
-    );
+    if payment == 0 {
+        assert!(
+            required_cost <= attached_deposit,
+            "Must attach {} yoctoNEAR to cover storage",
+            required_cost,
+        );
+    } else {
+        assert!(
+            required_cost <= attached_deposit,
+            "Must attach {} yoctoNEAR to cover storage ({}) and the mint price ({})",
+            required_cost,
+            storage_cost,
+            payment,
+        );
+    }
 
     //get the refund amount from the attached deposit - required cost
     let refund = attached_deposit - required_cost;
 
-    //if the refund is greater than 1 yocto NEAR, we refund the predecessor that amount
+    //if the refund is greater than 1 yocto NEAR, we refund the account that amount
     if refund > 1 {
-        Promise::new(env::predecessor_account_id()).transfer(refund);
+        Promise::new(account_id.clone()).transfer(refund);
     }
 }
 
+//refund the cost of the storage released by the contract to the given account
+pub(crate) fn refund_released_storage(account_id: &AccountId, storage_released: u64) {
+    let refund = env::storage_byte_cost() * Balance::from(storage_released);
+    if refund > 0 {
+        Promise::new(account_id.clone()).transfer(refund);
+    }
+}
+
+//check whether a token is within its validity window at the given timestamp:
+//it must have started (starts_at) and must not have expired yet (expires_at)
+pub(crate) fn token_is_valid(metadata: &TokenMetadata, timestamp: u64) -> bool {
+    let started = metadata.starts_at.map_or(true, |starts_at| starts_at <= timestamp);
+    let not_expired = metadata.expires_at.map_or(true, |expires_at| timestamp < expires_at);
+    started && not_expired
+}
+
 //convert the royalty percentage and amount to pay into a payout (U128)
 //we let the minimum royalty percentage be 0.01%, that is 1/10.000
 pub (crate) fn royalty_to_payout(royalty_percentage: u32, amount_to_pay: Balance) -> U128 {
@@ -172,14 +187,26 @@ impl Contract {
         //APPROVAL
         //if the sender doesn't equal the owner, we panic
         if sender_id != &token.owner_id { 
-            //if the token's approved account IDs doesn't contain the sender, we panic
-            if !token.approved_account_ids.contains_key(sender_id) {
+            //if the token's approved account IDs doesn't contain the sender
+            //and the sender isn't an operator of the owner, we panic
+            let sender_approval_id = token.approved_account_ids.get(sender_id);
+            let sender_is_operator = self.internal_is_operator(&token.owner_id, sender_id);
+            if sender_approval_id.is_none() && !sender_is_operator {
                 env::panic_str("Unauthorized");
             }
+
+            //if the sender's approval has an expiry that has passed, we panic,
+            //unless the sender is an operator of the owner, which doesn't need the approval
+            if let Some(sender_approval_id) = sender_approval_id {
+                if !sender_is_operator && self.approval_expired(token_id, *sender_approval_id) {
+                    env::panic_str("Approval expired");
+                }
+            }
             
             //If they included an approval_id, check if the sender's
-            //actual approval_id is the same as the one included
-            if let Some(enforced_approval_id) =  approval_id {
+            //actual approval_id is the same as the one included.
+            //Operators don't need a per-token approval, so their approval_id isn't checked
+            if let Some(enforced_approval_id) = approval_id.filter(|_| !sender_is_operator) {
                 //get the actual approval ID
                 let actual_approval_id = token.approved_account_ids.get(sender_id)
                 //if the sender isn't in the map we panic 
@@ -224,8 +251,8 @@ impl Contract {
         //APPROVAL
         //default the authorized ID to be None for the logs
         let mut authorized_id = None;
-        //if the approval ID was provided, set the authorized ID equal to the sender
-        if approval_id.is_some() {
+        //if the approval ID was provided or an operator made the transfer, set the authorized ID equal to the sender
+        if approval_id.is_some() || self.internal_is_operator(&token.owner_id, sender_id) {
             authorized_id = Some(sender_id.to_string());
         }
 
@@ -3,7 +3,7 @@ use near_sdk::collections::{LazyOption, LookupMap, LookupSet, UnorderedMap, Unor
 use near_sdk::json_types::{Base64VecU8, U128};
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::{
-    env, near_bindgen, AccountId, Balance, CryptoHash, PanicOnDefault, Promise, PromiseOrValue,
+    env, near_bindgen, AccountId, Balance, CryptoHash, PanicOnDefault, Promise, PromiseOrValue, PublicKey,
 };
 use std::collections::HashMap;
 
@@ -15,6 +15,17 @@ pub use crate::nft_core::*;
 pub use crate::events::*;
 pub use crate::approval::*;
 pub use crate::whitelist::*;
+pub use crate::validity::*;
+pub use crate::timestamps::*;
+pub use crate::svg::*;
+pub use crate::indexes::*;
+pub use crate::policy::*;
+pub use crate::voucher::*;
+pub use crate::treasury::*;
+pub use crate::operators::*;
+pub use crate::scores::*;
+pub use crate::score_check::*;
+pub use crate::migrate::*;
 
 mod enumerate;
 mod internal;
@@ -24,11 +35,26 @@ mod nft_core;
 mod events;
 mod approval;
 mod whitelist;
+mod validity;
+mod timestamps;
+mod svg;
+mod indexes;
+mod policy;
+mod voucher;
+mod treasury;
+mod operators;
+mod scores;
+mod score_check;
+mod migrate;
+mod royalty;
 
 //Declare the version of the standard
 pub const NFT_METADATA_SPEC: &str = "1.0.0";
 //Declare the name of the NFT standard we're using
 pub const NFT_STANDARD_NAME: &str = "nep171";
+//Declare the name and version of the contract-specific events (not part of any NEP)
+pub const ORACLE_STANDARD_NAME: &str = "nearoracle";
+pub const ORACLE_EVENT_VERSION: &str = "1.0.0";
 
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
@@ -48,8 +74,65 @@ pub struct Contract {
     //metadata for the contract
     pub metadata: LazyOption<NFTContractMetadata>,
 
-    //whitelist of users allowed to call the nft_mint() function
-    pub whitelist: LookupSet<AccountId>,
+    //whitelist of users allowed to call the nft_mint() function, with what each of them can mint
+    pub whitelist: UnorderedMap<AccountId, WhitelistGrant>,
+
+    //default lifetime (in nanoseconds) applied at mint to tokens that don't set their own expires_at
+    pub default_token_lifetime: Option<u64>,
+
+    //whether the per-user cap in nft_mint() should only count tokens that haven't expired yet
+    pub mint_cap_ignores_expired: bool,
+
+    //whether nft_mint() renders the score card SVG on chain instead of using the 'media' URI
+    pub onchain_media: bool,
+
+    //last time (in nanoseconds) a token was minted to a given account
+    pub last_minted_at: LookupMap<AccountId, u64>,
+
+    //first token minted with a given media content hash
+    pub tokens_by_media_hash: LookupMap<Vec<u8>, TokenId>,
+
+    //(receiver, media content hash) pairs that were already minted
+    pub media_hashes_per_owner: LookupSet<CryptoHash>,
+
+    //whether the same media content can be minted only once on the whole contract
+    pub unique_media_contract_wide: bool,
+
+    //limits enforced by nft_mint(): per-user cap, global cap and cooldown
+    pub mint_policy: MintPolicy,
+
+    //ed25519 public key mint vouchers must be signed with, None disables lazy minting
+    pub voucher_signer: Option<PublicKey>,
+
+    //nonces of the mint vouchers that were already redeemed
+    pub used_voucher_nonces: LookupSet<u64>,
+
+    //mint price per token type
+    pub mint_prices: UnorderedMap<String, MintPrice>,
+
+    //NEP-141 tokens mint prices can be paid in
+    pub accepted_fts: UnorderedSet<AccountId>,
+
+    //fungible tokens transferred in by each account and not yet spent, keyed by (FT contract, account)
+    pub ft_credits: LookupMap<(AccountId, AccountId), Balance>,
+
+    //revenue collected from mint fees per currency (None is NEAR), kept apart from storage deposits
+    pub treasury: UnorderedMap<Option<AccountId>, TreasuryBalance>,
+
+    //expiry (in nanoseconds) of the token approvals that have one, keyed by (token ID, approval ID)
+    pub approval_expiries: LookupMap<(TokenId, u64), u64>,
+
+    //operators approved to transfer all the tokens of a given owner
+    pub operators_per_owner: LookupMap<AccountId, UnorderedSet<AccountId>>,
+
+    //typed score carried by a given token ID, read from its metadata at mint
+    pub score_by_token: LookupMap<TokenId, TokenScore>,
+
+    //storescore contract nft_mint() checks the receiver's latest score against, None disables the check
+    pub score_contract_id: Option<AccountId>,
+
+    //score bands labelling the on-chain score cards, synced from the storescore contract
+    pub score_bands: Vec<ScoreBandEntry>,
 }
 /*
 Notice: the 'Contract' struct comprises of some custom data types, which we'll summarize here below:
@@ -71,6 +154,20 @@ pub enum StorageKey {
     TokenTypesLocked,
     WhiteList,
     MediaHash,
+    LastMintedAt,
+    MediaHashPerOwner,
+    UsedVoucherNonces,
+    MintPrices,
+    AcceptedFts,
+    FtCredits,
+    Treasury,
+    //reserved: formerly used by the whitelist grants map, do not reuse
+    WhitelistGrants,
+    WhitelistEntries,
+    ApprovalExpiries,
+    OperatorsPerOwner,
+    OperatorsPerOwnerInner { account_id_hash: CryptoHash },
+    ScoreByToken,
 }
 
 #[near_bindgen]
@@ -122,7 +219,47 @@ impl Contract {
                 StorageKey::TokenMetadataById.try_to_vec().unwrap(),
             ),
 
-            whitelist: LookupSet::new(StorageKey::WhiteList.try_to_vec().unwrap()),
+            whitelist: UnorderedMap::new(StorageKey::WhitelistEntries.try_to_vec().unwrap()),
+
+            //tokens never expire unless the owner configures a default lifetime
+            default_token_lifetime: None,
+
+            mint_cap_ignores_expired: false,
+
+            onchain_media: false,
+
+            last_minted_at: LookupMap::new(StorageKey::LastMintedAt.try_to_vec().unwrap()),
+
+            tokens_by_media_hash: LookupMap::new(StorageKey::MediaHash.try_to_vec().unwrap()),
+
+            media_hashes_per_owner: LookupSet::new(StorageKey::MediaHashPerOwner.try_to_vec().unwrap()),
+
+            unique_media_contract_wide: false,
+
+            mint_policy: MintPolicy::default(),
+
+            voucher_signer: None,
+
+            used_voucher_nonces: LookupSet::new(StorageKey::UsedVoucherNonces.try_to_vec().unwrap()),
+
+            //minting is free until the owner sets a price
+            mint_prices: UnorderedMap::new(StorageKey::MintPrices.try_to_vec().unwrap()),
+
+            accepted_fts: UnorderedSet::new(StorageKey::AcceptedFts.try_to_vec().unwrap()),
+
+            ft_credits: LookupMap::new(StorageKey::FtCredits.try_to_vec().unwrap()),
+
+            treasury: UnorderedMap::new(StorageKey::Treasury.try_to_vec().unwrap()),
+
+            approval_expiries: LookupMap::new(StorageKey::ApprovalExpiries.try_to_vec().unwrap()),
+
+            operators_per_owner: LookupMap::new(StorageKey::OperatorsPerOwner.try_to_vec().unwrap()),
+
+            score_by_token: LookupMap::new(StorageKey::ScoreByToken.try_to_vec().unwrap()),
+
+            score_contract_id: None,
+
+            score_bands: default_score_bands(),
         };
 
         //return the Contract object
@@ -14,14 +14,14 @@ pub struct NFTContractMetadata {
     pub spec: String,              // required, essentially a version like "nft-1.0.0"
     pub name: String,              // required, ex. "Mosaics"
     pub symbol: String,            // required, ex. "MOSIAC"
-    pub timestamp: u64,
+    pub timestamp: u64,            // when the contract was initialized, Unix epoch in nanoseconds
     pub icon: Option<String>,      // Data URL
     pub base_uri: String, // Centralized gateway known to have reliable access to decentralized storage assets referenced by `reference` or `media` URLs
     pub reference: Option<String>, // URL to a JSON file with more info
     pub reference_hash: Option<Base64VecU8>, // Base64-encoded sha256 hash of JSON from reference field. Required if `reference` is included.
 }
 
-#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
 #[serde(crate = "near_sdk::serde")]
 pub struct TokenMetadata {
     pub title: String, // ex. "Arch Nemesis: Mail Carrier" or "Parcel #5055"
@@ -29,10 +29,16 @@ pub struct TokenMetadata {
     pub media: String, // URL to associated media, preferably to decentralized, content-addressed storage
     pub media_hash: Option<Base64VecU8>, // Base64-encoded sha256 hash of content referenced by the `media` field. Required if `media` is included.
     pub copies: Option<u64>, // number of copies of this set of metadata in existence when token was minted.
-    pub issued_at: Option<u64>, // When token was issued or minted, Unix epoch in milliseconds
-    pub expires_at: Option<u64>, // When token expires, Unix epoch in milliseconds
-    pub starts_at: Option<u64>, // When token starts being valid, Unix epoch in milliseconds
-    pub updated_at: Option<u64>, // When token was last updated, Unix epoch in milliseconds
+    //the four timestamps below are stored as Unix epochs in nanoseconds, and are read from and
+    //written to JSON as NEP-177 strings holding Unix epochs in milliseconds (see timestamps.rs)
+    #[serde(default, with = "millis_string")]
+    pub issued_at: Option<u64>, // When token was issued or minted
+    #[serde(default, with = "millis_string")]
+    pub expires_at: Option<u64>, // When token expires
+    #[serde(default, with = "millis_string")]
+    pub starts_at: Option<u64>, // When token starts being valid
+    #[serde(default, with = "millis_string")]
+    pub updated_at: Option<u64>, // When token was last updated
     pub extra: Option<String>, // anything extra the NFT wants to store on-chain. Can be stringified JSON.
     pub reference: Option<String>, // URL to an off-chain JSON file with more info.
     pub reference_hash: Option<Base64VecU8>, // Base64-encoded sha256 hash of JSON from reference field. Required if `reference` is included.
@@ -69,13 +75,41 @@ pub struct JsonToken {
 
 // was the operation of minting a score as NFT successful?
 // the struct below describes the operation outcome
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 #[serde(crate = "near_sdk::serde")]
 pub struct MintOutcome {
     pub gas_used: Gas,
     pub nft_id: TokenId,
     pub owner_id: AccountId,
     pub successful_operation: bool,
+    //why the operation failed (only set for the skipped requests of a best-effort batch
+    //and for the mints refused by the score check)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+//a single request of a batch mint: the same arguments nft_mint() takes
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MintRequest {
+    pub token_id: TokenId,
+    pub metadata: TokenMetadata,
+    pub receiver_id: AccountId,
+    #[serde(default)]
+    pub perpetual_royalties: Option<HashMap<AccountId, u32>>,
+    //the type of the token, which sets its mint price
+    #[serde(default)]
+    pub token_type: Option<String>,
+}
+
+//how a batch mint behaves when one of its requests fails:
+//either the whole batch fails, or the failing requests are skipped
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum BatchMintMode {
+    AllOrNothing,
+    BestEffort,
 }
 
 /*
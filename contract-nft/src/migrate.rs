@@ -0,0 +1,47 @@
+use crate::*;
+
+/*
+State migration.
+The state of a contract deployed with the original version of this code only holds the fields
+of OldContract below: redeploying the current code on top of it requires calling migrate()
+in the same transaction, else every call fails to deserialize the state.
+migrate() keeps the tokens, their metadata and the contract metadata, and initializes every
+field added since then the way new() does.
+The original whitelist was a LookupSet, which can't be listed: the accounts it held are not
+carried over, and the owner grants them again with add_to_whitelist().
+Once migrated, run migrate_token_timestamps() and index_minted_tokens() over every page of tokens.
+ */
+
+//the contract state as laid out by the original version of this code
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct OldContract {
+    pub owner_id: AccountId,
+    pub tokens_per_owner: LookupMap<AccountId, UnorderedSet<TokenId>>,
+    pub token_by_id: LookupMap<TokenId, Token>,
+    pub token_metadata_by_id: UnorderedMap<TokenId, TokenMetadata>,
+    pub metadata: LazyOption<NFTContractMetadata>,
+    pub whitelist: LookupSet<AccountId>,
+}
+
+#[near_bindgen]
+impl Contract {
+    /*
+    Migrate the state written by the original version of this code to the current layout.
+    This method can be called only by the contract account itself, e.g., in the deploy transaction.
+     */
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let old: OldContract = env::state_read().expect("Migration error: no state to migrate");
+        let metadata = old.metadata.get().expect("Migration error: missing contract metadata");
+
+        Self {
+            tokens_per_owner: old.tokens_per_owner,
+            token_by_id: old.token_by_id,
+            token_metadata_by_id: old.token_metadata_by_id,
+            metadata: old.metadata,
+            //every field added since the original version starts out as new() sets it
+            ..Self::new(old.owner_id, metadata)
+        }
+    }
+}
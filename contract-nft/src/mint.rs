@@ -1,15 +1,27 @@
 use crate::*;
 use near_sdk::{log, env};
-use near_sdk::json_types::Base64VecU8;
+use near_sdk::json_types::{Base64VecU8, U64};
 
-const MAXOUT_USER_NFTS: i8 = 12;
-const MAXOUT_CONTRACT_NTFS: i32 = 100;
+//max number of tokens that can be minted in a single nft_batch_mint() call
+const MAXOUT_BATCH_MINT: usize = 10;
+
+//a mint request that passed all the checks and is ready to be written to the contract state
+pub(crate) struct PreparedMint {
+    pub token_id: TokenId,
+    pub token: Token,
+    pub metadata: TokenMetadata,
+    pub media_hash: Vec<u8>,
+    pub price: Option<MintPrice>,
+    pub score: Option<u16>,
+}
 
 #[near_bindgen]
 impl Contract {
     #[payable]
     //mint a token as an NFT and returns a struct indicating
-    //whether the minting operation was successful
+    //whether the minting operation was successful.
+    //When a score contract is configured, the score is first checked against it
+    //and the outcome is returned by the callback completing the mint.
     pub fn nft_mint(
         &mut self,
         token_id: TokenId,
@@ -17,58 +29,318 @@ impl Contract {
         receiver_id: AccountId,
         //we add an optional parameter for perpetual royalties
         perpetual_royalties: Option<HashMap<AccountId, u32>>,
-) -> MintOutcome {
+        //the type of the token, which sets its mint price
+        token_type: Option<String>,
+) -> PromiseOrValue<MintOutcome> {
         //measure the initial storage being used on the contract
         let initial_storage_usage = env::storage_usage();
 
         //WHITELIST CHECK
-        //the account invoking the nft_mint() function must either be the
-        //contract owner or a whitelisted account id
-        if &env::signer_account_id() != &self.owner_id {
-            assert!(
-                self.whitelist.contains(&env::signer_account_id()),
-                "Only whitelisted accounts can call this function"
-            );
+        self.assert_can_mint();
+
+        let request = MintRequest {
+            token_id,
+            metadata,
+            receiver_id,
+            perpetual_royalties,
+            token_type,
         };
 
+        //SCORE CHECK
+        //the score must match the receiver's latest score on the score contract, if one is configured
+        if let Some(score_contract_id) = self.score_contract_id.clone() {
+            return self.internal_mint_with_score_check(score_contract_id, request).into();
+        }
+
         //LOGIC CHECKS
-        //set max limit to the number of NFTs minted per user
-        assert!(
-            self.nft_supply_for_owner(&receiver_id) < U128(MAXOUT_USER_NFTS as u128),
-            "You can mint a limited amount of NFTs per user. You exceeded that limit"
-        );
-        //set max limit to the total number of NFTs minted by the contract since first deployment
+        //run every check of the mint policy, and panic with the reason if one fails
+        let prepared = self
+            .internal_prepare_mint(request, &env::predecessor_account_id())
+            .unwrap_or_else(|error| env::panic_str(&error));
+
+        //the mint must be allowed by the whitelist grant of the caller, which is consumed
+        self.internal_check_grant(&prepared).unwrap_or_else(|error| env::panic_str(&error));
+        self.internal_use_grant();
+
+        //PAYMENT
+        //collect the mint price into the treasury
+        let payment = self.internal_collect_mint_price(&env::predecessor_account_id(), &prepared.price);
+
+        //CORE
+        let (token_id, owner_id) = self.internal_mint(prepared);
+
+        //LOG EVENTS
+        //construct the mint log as per the events standard
+        log_nft_mint(vec![NftMintLog {
+            //token owner
+            owner_id: owner_id.to_string(),
+            //vector of token IDs that were minted
+            token_ids: vec![token_id.to_string()],
+            //an optional memo to include
+            memo: None,
+        }]);
+
+        //PAYOUT
+        //calculate the required storage = used storage - initial storage
+        let required_storage_in_bytes = env::storage_usage() - initial_storage_usage;
+
+        //refund surplus storage to user OR panic if they didn't attach enough to cover for the storage and the mint price
+        refund_deposit_after_payment(required_storage_in_bytes, payment);
+
+        //remove the whitelist grant of the caller if it's used up
+        self.internal_remove_used_grant();
+
+        // return an outcome struct describing whether the
+        // operation of minting a score as NFT was successful
+        let success = match self.whose_token((*token_id).to_string()) {
+            Some(x) if x == owner_id => true,
+            _ => false,
+        };
+        PromiseOrValue::Value(MintOutcome {
+            gas_used: env::used_gas(),
+            nft_id: token_id,
+            owner_id,
+            successful_operation: success,
+            error: None,
+        })
+    }
+
+    #[payable]
+    //mint several tokens in a single transaction and return one outcome per request.
+    //Every request goes through the same checks as nft_mint(), and the storage is charged for the batch as a whole.
+    //In 'all_or_nothing' mode (the default) the whole batch fails if one request fails,
+    //whereas in 'best_effort' mode the failing requests are skipped and reported in their outcome.
+    pub fn nft_batch_mint(
+        &mut self,
+        requests: Vec<MintRequest>,
+        mode: Option<BatchMintMode>,
+    ) -> Vec<MintOutcome> {
+        //measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        //WHITELIST CHECK
+        self.assert_can_mint();
+
+        assert!(!requests.is_empty(), "The batch must contain at least one mint request");
         assert!(
-            self.nft_total_supply() < U128(MAXOUT_CONTRACT_NTFS as u128),
-            "The contract can mint a limited amount of NFTs. You exceeded that limit"
-        );
-        //a user can mint at most 1 score per month
-        //you can't mint the same score twice
-        let nfts = self.nft_tokens_for_owner(
-            &receiver_id,
-            None,
-            None
+            requests.len() <= MAXOUT_BATCH_MINT,
+            "Cannot mint more than {} NFTs in a single batch",
+            MAXOUT_BATCH_MINT
         );
-        if nfts.len() >= 1 {
-            let unixtimes = nfts
-                .iter()
-                .map(|x| x.metadata.issued_at.unwrap()).collect::<Vec<u64>>(); 
+        //batches can't wait for the score of each request to be checked
+        if let Some(score_contract_id) = &self.score_contract_id {
+            env::panic_str(&format!(
+                "Batch minting is unavailable while scores are checked against {}",
+                score_contract_id
+            ));
+        }
+        let mode = mode.unwrap_or(BatchMintMode::AllOrNothing);
 
-            let timelapsed = env::block_timestamp() - unixtimes.iter().max().unwrap();
+        //CORE
+        //the mint logs are grouped per owner, in the order the owners first appear in the batch
+        let mut mint_logs: Vec<NftMintLog> = vec![];
+        let mut outcomes = vec![];
+        let mut payment: Balance = 0;
+        for request in requests {
+            let token_id = request.token_id.clone();
+            let receiver_id = request.receiver_id.clone();
+
+            //each request is checked against the state left by the previous ones,
+            //and consumes one use of the whitelist grant of the caller
+            //and must fit, with its NEAR price and its storage, in what is left of the deposit
+            let minted = self
+                .internal_prepare_mint(request, &env::predecessor_account_id())
+                .and_then(|prepared| self.internal_check_grant(&prepared).map(|_| prepared))
+                .and_then(|prepared| self.internal_mint_within_deposit(prepared, initial_storage_usage, &mut payment));
+            match minted {
+                Ok((token_id, owner_id)) => {
+                    self.internal_use_grant();
+                    match mint_logs.iter_mut().find(|log| log.owner_id == owner_id.as_str()) {
+                        Some(log) => log.token_ids.push(token_id.to_string()),
+                        None => mint_logs.push(NftMintLog {
+                            owner_id: owner_id.to_string(),
+                            token_ids: vec![token_id.to_string()],
+                            memo: None,
+                        }),
+                    }
+                    outcomes.push(MintOutcome {
+                        gas_used: env::used_gas(),
+                        nft_id: token_id,
+                        owner_id,
+                        successful_operation: true,
+                        error: None,
+                    });
+                }
+                Err(error) => match mode {
+                    BatchMintMode::AllOrNothing => env::panic_str(&format!(
+                        "Batch mint failed on token {}: {}",
+                        token_id, error
+                    )),
+                    BatchMintMode::BestEffort => outcomes.push(MintOutcome {
+                        gas_used: env::used_gas(),
+                        nft_id: token_id,
+                        owner_id: receiver_id,
+                        successful_operation: false,
+                        error: Some(error),
+                    }),
+                },
+            }
+        }
+
+        //LOG EVENTS
+        //a single nft_mint event covers the whole batch
+        if !mint_logs.is_empty() {
+            log_nft_mint(mint_logs);
+        }
+
+        //PAYOUT
+        //calculate the storage required by the whole batch and refund the surplus
+        //(the NEAR mint prices of the whole batch are charged on the same deposit,
+        //which covers them since every token was checked against it)
+        let required_storage_in_bytes = env::storage_usage() - initial_storage_usage;
+        refund_deposit_after_payment(required_storage_in_bytes, payment);
+
+        //remove the whitelist grant of the caller if it's used up
+        self.internal_remove_used_grant();
+
+        outcomes
+    }
+}
+
+impl Contract {
+    //the account invoking a mint function must either be the
+    //contract owner or a whitelisted account id
+    //(what the account can mint is checked against its grant for each token)
+    pub(crate) fn assert_can_mint(&self) {
+        if &env::signer_account_id() != &self.owner_id {
             assert!(
-                //timelapsed > 60 * u64::pow(10, 9), //60 sec
-                timelapsed > 2592 * u64::pow(10, 12), //30 days
-                "Limit exceeded: you can mint at most one score per month"
+                self.whitelist.get(&env::signer_account_id()).is_some(),
+                "Only whitelisted accounts can call this function"
             );
-            for n in nfts {
-                assert!(
-                    &metadata.media != &n.metadata.media,
-                    "Duplicate error: you can't mint the same NFT twice"
-                );
+        };
+    }
+
+    //complete the metadata of a mint request and run every check of the mint policy on it,
+    //checking that the payer can afford the mint price.
+    //Returns the reason of the failure instead of panicking, so that batches can skip failing requests.
+    pub(crate) fn internal_prepare_mint(&self, request: MintRequest, payer_id: &AccountId) -> Result<PreparedMint, String> {
+        let MintRequest {
+            token_id,
+            metadata,
+            receiver_id,
+            perpetual_royalties,
+            token_type,
+        } = request;
+
+        //make sure that the token doesn't exist
+        if self.token_by_id.contains_key(&token_id) {
+            return Err("Token already exists".to_string());
+        }
+
+        //METADATA
+        //save minting timestamp (in nanoseconds) among the attributes of the TokenMetadata
+        let mut meta = metadata;
+        meta.issued_at = Some(env::block_timestamp());
+
+        //VALIDITY WINDOW
+        //tokens that don't set their own expiry get the default lifetime, if one is configured
+        if meta.expires_at.is_none() {
+            meta.expires_at = self
+                .default_token_lifetime
+                .map(|lifetime| env::block_timestamp() + lifetime);
+        }
+        if let Some(expires_at) = meta.expires_at {
+            if expires_at <= env::block_timestamp() {
+                return Err("Validity error: you can't mint a token that has already expired".to_string());
+            }
+            if let Some(starts_at) = meta.starts_at {
+                if starts_at >= expires_at {
+                    return Err("Validity error: starts_at must come before expires_at".to_string());
+                }
+            }
+        }
+
+        //SCORE
+        //the score held in 'extra', if any, is also stored as typed token data
+        let score = score_from_extra(&meta.extra);
+
+        //MEDIA
+        if self.onchain_media {
+            //render the score card on chain and hash the actual bytes of the image
+            let score = score.ok_or_else(|| {
+                "On-chain media error: metadata.extra must hold the score, e.g. {\"score\": 712}".to_string()
+            })?;
+            let svg = render_score_card(score, score_band(&self.score_bands, score), &receiver_id, env::block_timestamp());
+            meta.media = svg_data_uri(&svg);
+            meta.media_hash = Some(Base64VecU8(env::sha256(svg.as_bytes())));
+        } else {
+            //the caller must supply the sha256 hash of the content the media URI points at
+            match &meta.media_hash {
+                None => {
+                    return Err("Hash error: media_hash is required, it must be the sha256 hash of the media content".to_string())
+                }
+                Some(media_hash) if media_hash.0.len() != 32 => {
+                    return Err("Hash error: media_hash must be a sha256 hash".to_string())
+                }
+                _ => {}
+            }
+        }
+
+        //REFERENCE
+        //as per NEP-177, reference_hash is required whenever a reference is included
+        if meta.reference.is_some() {
+            match &meta.reference_hash {
+                None => return Err("Hash error: reference_hash is required when reference is set".to_string()),
+                Some(reference_hash) if reference_hash.0.len() != 32 => {
+                    return Err("Hash error: reference_hash must be a sha256 hash".to_string())
+                }
+                _ => {}
+            }
+        }
+
+        //POLICY CHECKS
+        //set max limit to the number of NFTs minted per user
+        //(expired tokens can optionally be left out of the count)
+        let owned_nfts = if self.mint_cap_ignores_expired {
+            self.nft_valid_supply_for_owner(&receiver_id)
+        } else {
+            self.nft_supply_for_owner(&receiver_id)
+        };
+        if owned_nfts >= U128(self.mint_policy.max_tokens_per_user as u128) {
+            return Err("You can mint a limited amount of NFTs per user. You exceeded that limit".to_string());
+        }
+        //set max limit to the total number of NFTs minted by the contract since first deployment
+        //(the contract has no limit when max_total_supply is None)
+        if let Some(max_total_supply) = self.mint_policy.max_total_supply {
+            if self.nft_total_supply() >= U128(max_total_supply as u128) {
+                return Err("The contract can mint a limited amount of NFTs. You exceeded that limit".to_string());
+            }
+        }
+        //a user can mint at most 1 score per cooldown period (30 days by default)
+        if let Some(last_minted_at) = self.last_minted_at.get(&receiver_id) {
+            let timelapsed = env::block_timestamp() - last_minted_at;
+            if timelapsed < self.mint_policy.cooldown.0 {
+                return Err("Limit exceeded: you can mint at most one score per cooldown period".to_string());
             }
         } else {
             log!("New user");
         };
+        //you can't mint the same score twice
+        //duplicates are detected on the content hash, so two URIs pointing at the same bytes match
+        let media_hash = meta.media_hash.as_ref().unwrap().0.clone();
+        if self.unique_media_contract_wide && self.tokens_by_media_hash.contains_key(&media_hash) {
+            return Err("Duplicate error: this NFT was already minted on the contract".to_string());
+        }
+        if self.media_hashes_per_owner.contains(&hash_owner_media(&receiver_id, &media_hash)) {
+            return Err("Duplicate error: you can't mint the same NFT twice".to_string());
+        }
+
+        //PRICE
+        //the caller pays the mint price of the token type, if one is set
+        let price = self.internal_mint_price(&token_type);
+        if let Some(price) = &price {
+            self.internal_check_mint_price(payer_id, price)?;
+        }
 
         //ROYALTY
         //create a royalty map to store in the token
@@ -78,7 +350,9 @@ impl Contract {
         if let Some(perpetual_royalties) = perpetual_royalties {
             //make sure that the length of the perpetual royalties is below 5
             //since we won't have enough GAS to pay out that many people
-            assert!(perpetual_royalties.len() < 5, "Cannot add more than 4 perpetual royalty amounts");
+            if perpetual_royalties.len() >= 5 {
+                return Err("Cannot add more than 4 perpetual royalty amounts".to_string());
+            }
 
             //iterate through the perpetual royalties and insert the account and amount in the royalty map
             for (account, amount) in perpetual_royalties {
@@ -86,7 +360,6 @@ impl Contract {
             }
         };
 
-        //CORE
         //specify the token struct that contains the owner ID
         let token = Token {
             //set owner ID to be equal to the receiver ID
@@ -99,6 +372,68 @@ impl Contract {
             royalty,
         };
 
+        Ok(PreparedMint {
+            token_id,
+            token,
+            metadata: meta,
+            media_hash,
+            price,
+            score,
+        })
+    }
+
+    //collect the mint price of a prepared token and mint it, as long as the deposit covers the storage
+    //used since the batch started and the NEAR prices paid so far, this token included.
+    //Otherwise the token and its price are rolled back, and the reason is returned.
+    fn internal_mint_within_deposit(
+        &mut self,
+        prepared: PreparedMint,
+        initial_storage_usage: u64,
+        payment: &mut Balance,
+    ) -> Result<(TokenId, AccountId), String> {
+        let payer_id = env::predecessor_account_id();
+        let price = prepared.price.clone();
+        let media_hash = prepared.media_hash.clone();
+        let last_minted_at = self.last_minted_at.get(&prepared.token.owner_id);
+
+        let token_payment = self.internal_collect_mint_price(&payer_id, &price);
+        let (token_id, owner_id) = self.internal_mint(prepared);
+
+        let storage_cost = env::storage_byte_cost() * Balance::from(env::storage_usage() - initial_storage_usage);
+        let required_cost = storage_cost + *payment + token_payment;
+        if required_cost > env::attached_deposit() {
+            self.internal_unmint(&token_id, &owner_id, &media_hash, last_minted_at);
+            self.internal_cancel_mint_price(&payer_id, &price);
+            return Err(format!(
+                "Payment error: the batch requires {} yoctoNEAR up to this token, but only {} was attached",
+                required_cost,
+                env::attached_deposit()
+            ));
+        }
+        *payment += token_payment;
+        Ok((token_id, owner_id))
+    }
+
+    //remove a token that was just minted by internal_mint(), given the last mint time of its owner before that mint
+    fn internal_unmint(&mut self, token_id: &TokenId, owner_id: &AccountId, media_hash: &[u8], last_minted_at: Option<u64>) {
+        self.token_by_id.remove(token_id);
+        self.token_metadata_by_id.remove(token_id);
+        self.score_by_token.remove(token_id);
+        self.internal_remove_token_from_owner(owner_id, token_id);
+        self.internal_unindex_mint(owner_id, token_id, media_hash, last_minted_at);
+    }
+
+    //write a prepared token to the contract state and return its token ID and owner
+    pub(crate) fn internal_mint(&mut self, prepared: PreparedMint) -> (TokenId, AccountId) {
+        let PreparedMint {
+            token_id,
+            token,
+            metadata,
+            media_hash,
+            score,
+            ..
+        } = prepared;
+
         //insert the token ID and the token struct,
         //but first make sure that the token doen't exist -> do this latter part by using
         //the 'assert!' macro with a custom panic message
@@ -107,61 +442,38 @@ impl Contract {
             "Token already exists"
         );
 
-        //save minting timestamp among the attributes of the TokenMetadata
-        let mut meta = metadata;
-        meta.issued_at = Some(env::block_timestamp());
-        //hash the the token's metadata.media uri using the sha256 function
-        let hash = Base64VecU8(env::sha256(meta.media.as_bytes()));
-        meta.media_hash = Some(hash);
         //insert token id and metadata
-        self.token_metadata_by_id.insert(&token_id, &meta);
+        self.token_metadata_by_id.insert(&token_id, &metadata);
 
+        //insert the typed score of the token, if any
+        if let Some(score) = score {
+            self.score_by_token.insert(&token_id, &TokenScore {
+                score,
+                issued_at: U64(metadata.issued_at.unwrap_or_else(env::block_timestamp)),
+            });
+        }
 
         //call an internal method to add a token to the owner
         self.internal_add_token_to_owner(&token.owner_id, &token_id);
 
-        //LOG EVENTS
-        //construct the mint log as per the events standard
-        let nft_mint_log: EventLog = EventLog {
-            //standard name ("nep171")
-            standard: NFT_STANDARD_NAME.to_string(),
-            //version of the standard ("nft-1.0.0")
-            version: NFT_METADATA_SPEC.to_string(),
-            //the data related with the event stored in a vector
-            event: EventLogVariant::NftMint(vec![NftMintLog {
-                //token owner
-                owner_id: token.owner_id.to_string(),
-                //vector of token IDs that were minted
-                token_ids: vec![token_id.to_string()],
-                //an optional memo to include
-                memo: None,
-            }])
-        };
+        //record the token in the indexes used by the cooldown and duplicate checks
+        self.internal_index_mint(&token.owner_id, &token_id, &media_hash, env::block_timestamp());
 
-        //log the serialized json
-        env::log_str(&nft_mint_log.to_string());
-
-        //PAYOUT
-        //calculate the required storage = used storage - initial storage
-        let required_storage_in_bytes = env::storage_usage() - initial_storage_usage;
-
-        //refund surplus storage to user OR panic if they didn't attach enough to cover for the required gas fee
-        refund_deposit(required_storage_in_bytes);
+        (token_id, token.owner_id)
+    }
+}
 
-        //remove the function caller from the contract whitelist
-        self.whitelist.remove(&env::signer_account_id());
+//log an nft_mint event as per the events standard
+pub(crate) fn log_nft_mint(mint_logs: Vec<NftMintLog>) {
+    let nft_mint_log: EventLog = EventLog {
+        //standard name ("nep171")
+        standard: NFT_STANDARD_NAME.to_string(),
+        //version of the standard ("nft-1.0.0")
+        version: NFT_METADATA_SPEC.to_string(),
+        //the data related with the event stored in a vector
+        event: EventLogVariant::NftMint(mint_logs),
+    };
 
-        // return an outcome struct describing whether the
-        // operation of minting a score as NFT was successful
-        let success = match self.whose_token((*token_id).to_string()) {
-            Some(x) if x == token.owner_id => true,
-            _ => false,
-        };
-        MintOutcome {
-            gas_used: env::used_gas(),
-            nft_id: token_id,
-            owner_id: token.owner_id,
-            successful_operation: success,
-        }
-    }
-}
\ No newline at end of file
+    //log the serialized json
+    env::log_str(&nft_mint_log.to_string());
+}
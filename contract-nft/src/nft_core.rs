@@ -51,7 +51,7 @@ trait NonFungibleTokenReceiver {
 
 
 #[ext_contract(ext_self)]
-trait NonFungibleTokenResolver {
+pub trait NonFungibleTokenResolver {
     /*
         resolves the promise of the cross contract call to the receiver contract
         this is stored on THIS contract and is meant to analyze what happened in the cross contract call when nft_on_transfer was called
@@ -119,8 +119,9 @@ impl NonFungibleTokenCore for Contract {
         );
 
         //we refund the owner for releasing the storage used up by the approved account IDs
-        refund_approved_account_ids(
+        self.internal_refund_approvals(
             previous_token.owner_id.clone(),
+            &token_id,
             &previous_token.approved_account_ids,
         );
     }
@@ -213,7 +214,7 @@ impl NonFungibleTokenResolver for Contract {
                     */
                     //case 1: the NFT transfer went through: all is well! All remains to do is refunding
                     //the old NFT owner for freeing storage space since we reset the approved_account_ids HashMap
-                    refund_approved_account_ids(owner_id, &approved_account_ids);
+                    self.internal_refund_approvals(owner_id, &token_id, &approved_account_ids);
                     return true;
                 }
             }
@@ -223,13 +224,13 @@ impl NonFungibleTokenResolver for Contract {
         let mut token = if let Some(token) = self.token_by_id.get(&token_id) {
             if token.owner_id != receiver_id {
                 //the token is not owned by the receiver anymore. Can't return it.
-                refund_approved_account_ids(owner_id, &approved_account_ids);
+                self.internal_refund_approvals(owner_id, &token_id, &approved_account_ids);
                 return true;
             }
             token
         //if there isn't a token object, it was burned and so we return true
         } else {
-            refund_approved_account_ids(owner_id, &approved_account_ids);
+            self.internal_refund_approvals(owner_id, &token_id, &approved_account_ids);
             return true;
         };
 
@@ -244,7 +245,7 @@ impl NonFungibleTokenResolver for Contract {
         token.owner_id = owner_id.clone();
 
         //we refund the receiver any approved account IDs that they may have set on the token
-        refund_approved_account_ids(receiver_id.clone(), &token.approved_account_ids);
+        self.internal_refund_approvals(receiver_id.clone(), &token_id, &token.approved_account_ids);
         //reset the approved account IDs to what they were before the transfer
         token.approved_account_ids = approved_account_ids;
 
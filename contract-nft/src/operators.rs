@@ -0,0 +1,142 @@
+use crate::*;
+
+/*
+Operator approvals.
+An owner can approve an operator (e.g. a wallet manager or a marketplace) to transfer all of
+their tokens, present and future, in a single call rather than approving it token by token.
+Operators are kept per owner, apart from the per-token approvals: they survive transfers
+(they just stop applying to the tokens the owner no longer holds) and carry no approval ID.
+The owner pays for the storage of their operators and gets it back when revoking them.
+ */
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    //approve an operator to transfer all of the caller's tokens on their behalf.
+    //Returns `true` if the operator was not approved before, `false` otherwise.
+    //The caller must attach enough NEAR to cover the storage of the approval.
+    pub fn nft_approve_operator(&mut self, operator_id: AccountId) -> bool {
+        //get the caller of the function, whose tokens the operator will manage
+        let owner_id = env::predecessor_account_id();
+        assert_ne!(owner_id, operator_id, "An owner can't be their own operator");
+
+        let initial_storage_usage = env::storage_usage();
+
+        //get the set of operators of the owner, or create it if they don't have any
+        let mut operators = self.operators_per_owner.get(&owner_id).unwrap_or_else(|| {
+            UnorderedSet::new(
+                StorageKey::OperatorsPerOwnerInner {
+                    //we get a new unique prefix for the collection
+                    account_id_hash: hash_account_id(&owner_id),
+                }
+                .try_to_vec()
+                .unwrap(),
+            )
+        });
+        let is_new_operator = operators.insert(&operator_id);
+        self.operators_per_owner.insert(&owner_id, &operators);
+
+        //refund any excess storage attached by the user. If the user didn't attach enough, panic.
+        refund_deposit(env::storage_usage() - initial_storage_usage);
+
+        //LOG EVENTS
+        if is_new_operator {
+            let operator_approve_log: EventLog = EventLog {
+                //standard name ("nearoracle")
+                standard: ORACLE_STANDARD_NAME.to_string(),
+                //version of the standard ("1.0.0")
+                version: ORACLE_EVENT_VERSION.to_string(),
+                //the data related with the event stored in a vector
+                event: EventLogVariant::NftOperatorApprove(vec![NftOperatorLog {
+                    owner_id: owner_id.to_string(),
+                    operator_id: operator_id.to_string(),
+                }]),
+            };
+
+            //log the serialized json
+            env::log_str(&operator_approve_log.to_string());
+        }
+
+        is_new_operator
+    }
+
+    #[payable]
+    //revoke an operator from transferring the caller's tokens and refund the storage it released.
+    //Returns `true` if the operator was approved, `false` otherwise.
+    pub fn nft_revoke_operator(&mut self, operator_id: AccountId) -> bool {
+        //assert that the caller attached exactly 1 yoctoNEAR for security
+        assert_one_yocto();
+        let owner_id = env::predecessor_account_id();
+
+        let mut operators = match self.operators_per_owner.get(&owner_id) {
+            Some(operators) => operators,
+            None => return false,
+        };
+
+        let initial_storage_usage = env::storage_usage();
+        if !operators.remove(&operator_id) {
+            return false;
+        }
+        //if the owner has no operators left, we remove their set altogether
+        if operators.is_empty() {
+            self.operators_per_owner.remove(&owner_id);
+        } else {
+            self.operators_per_owner.insert(&owner_id, &operators);
+        }
+
+        //refund the funds released by removing the operator to the owner
+        refund_released_storage(&owner_id, initial_storage_usage - env::storage_usage());
+
+        //LOG EVENTS
+        let operator_revoke_log: EventLog = EventLog {
+            //standard name ("nearoracle")
+            standard: ORACLE_STANDARD_NAME.to_string(),
+            //version of the standard ("1.0.0")
+            version: ORACLE_EVENT_VERSION.to_string(),
+            //the data related with the event stored in a vector
+            event: EventLogVariant::NftOperatorRevoke(vec![NftOperatorLog {
+                owner_id: owner_id.to_string(),
+                operator_id: operator_id.to_string(),
+            }]),
+        };
+
+        //log the serialized json
+        env::log_str(&operator_revoke_log.to_string());
+
+        true
+    }
+
+    //Query whether the given operator can transfer all of the owner's tokens
+    pub fn nft_is_operator(&self, owner_id: AccountId, operator_id: AccountId) -> bool {
+        self.internal_is_operator(&owner_id, &operator_id)
+    }
+
+    //Query the operators of the given owner, using pagination
+    pub fn nft_operators(&self, owner_id: AccountId, from_index: Option<U128>, limit: Option<u64>) -> Vec<AccountId> {
+        //get the set of operators for the passed in owner
+        let operators = if let Some(operators) = self.operators_per_owner.get(&owner_id) {
+            operators
+        } else {
+            //if there is no set of operators, we simply return an empty vector
+            return vec![];
+        };
+
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        operators
+            .iter()
+            .skip(start as usize)
+            .take(limit.unwrap_or(50) as usize)
+            .collect()
+    }
+}
+
+impl Contract {
+    //whether the given account is an operator of the given owner
+    pub(crate) fn internal_is_operator(&self, owner_id: &AccountId, operator_id: &AccountId) -> bool {
+        self.operators_per_owner
+            .get(owner_id)
+            .map_or(false, |operators| operators.contains(operator_id))
+    }
+}
@@ -0,0 +1,68 @@
+use crate::*;
+use near_sdk::json_types::U64;
+
+//the mint policy the contract is initialized with
+const DEFAULT_MAX_TOKENS_PER_USER: u32 = 12;
+const DEFAULT_MAX_TOTAL_SUPPLY: u64 = 100;
+const DEFAULT_MINT_COOLDOWN: u64 = 30 * NANOS_PER_DAY;
+
+//limits enforced by nft_mint(), persisted on chain and adjustable by the contract owner
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MintPolicy {
+    //max number of NFTs a single account can hold
+    pub max_tokens_per_user: u32,
+    //max number of NFTs the contract can mint, None means unlimited
+    pub max_total_supply: Option<u64>,
+    //min time (in nanoseconds) between two mints to the same account
+    pub cooldown: U64,
+}
+
+impl Default for MintPolicy {
+    fn default() -> Self {
+        Self {
+            max_tokens_per_user: DEFAULT_MAX_TOKENS_PER_USER,
+            max_total_supply: Some(DEFAULT_MAX_TOTAL_SUPPLY),
+            cooldown: U64(DEFAULT_MINT_COOLDOWN),
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    //Query the mint policy currently enforced by nft_mint()
+    pub fn mint_policy(&self) -> MintPolicy {
+        self.mint_policy.clone()
+    }
+
+    //Replaces the mint policy enforced by nft_mint() and logs the change.
+    //This method can be called only by the smart contract owner.
+    pub fn set_mint_policy(&mut self, policy: MintPolicy) {
+        //ensure the function was called by the smart contract owner, else panic
+        self.assert_called_by_owner();
+
+        assert!(
+            policy.max_tokens_per_user > 0,
+            "The per-user cap must allow at least one NFT"
+        );
+        self.mint_policy = policy.clone();
+
+        //LOG EVENTS
+        let mint_policy_log: EventLog = EventLog {
+            //standard name ("nearoracle")
+            standard: ORACLE_STANDARD_NAME.to_string(),
+            //version of the standard ("1.0.0")
+            version: ORACLE_EVENT_VERSION.to_string(),
+            //the data related with the event stored in a vector
+            event: EventLogVariant::MintPolicyUpdate(vec![MintPolicyUpdateLog {
+                //the account that changed the policy
+                updated_by: env::predecessor_account_id().to_string(),
+                //the policy in force from now on
+                policy,
+            }]),
+        };
+
+        //log the serialized json
+        env::log_str(&mint_policy_log.to_string());
+    }
+}
@@ -10,6 +10,7 @@ pub trait NonFungibleTokenCore {
     ) -> Payout;
     
     //transfers the token to the receiver ID and returns the payout object that should be payed given the passed in balance. 
+    //The approval_id is ignored when the caller is an operator of the owner
     fn nft_transfer_payout(
         &mut self,
         receiver_id: AccountId,
@@ -87,9 +88,10 @@ impl NonFungibleTokenCore for Contract {
             memo,
         );
 
-        //refund the previous token owner for the storage used up by the previous approved account IDs
-        refund_approved_account_ids(
+        //refund the previous token owner for the storage used up by the previous approved account IDs (and their expiries)
+        self.internal_refund_approvals(
             previous_token.owner_id.clone(),
+            &token_id,
             &previous_token.approved_account_ids,
         );
 
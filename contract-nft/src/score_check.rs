@@ -0,0 +1,306 @@
+use crate::*;
+use near_sdk::{ext_contract, Gas, PromiseResult};
+use near_sdk::serde_json::Value;
+
+/*
+Optional linkage with the storescore contract.
+When the owner configures a score contract, nft_mint() can only mint a score that was recorded
+there: it fetches the receiver's score history with a cross contract call, and a callback completes
+the mint only if the score and the timestamp held in the metadata ('extra' must hold a JSON object
+such as {"score": 712, "timestamp": 1650000000000000000}) match the receiver's latest score.
+
+The attached deposit travels along with the callback, which pays the storage and the mint price out
+of it. If the check fails, the callback refunds the whole deposit and logs an nft_mint_failure event.
+A last callback also refunds the deposit should the mint itself fail in the first callback.
+ */
+
+const GAS_FOR_QUERY_SCORE_HISTORY: Gas = Gas(10_000_000_000_000);
+const GAS_FOR_RESOLVE_SCORE_CHECK: Gas = Gas(40_000_000_000_000);
+const GAS_FOR_RESOLVE_SCORE_CHECKED_MINT: Gas = Gas(10_000_000_000_000);
+const GAS_FOR_QUERY_SCORE_BANDS: Gas = Gas(10_000_000_000_000);
+const GAS_FOR_RESOLVE_SCORE_BANDS: Gas = Gas(10_000_000_000_000);
+
+//a score recorded on the storescore contract
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RecordedScore {
+    pub score: u16,
+    //Unix epoch in nanoseconds
+    pub timestamp: u64,
+}
+
+//the score history of an account, as returned by the storescore contract
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RecordedScoreHistory {
+    pub scores: Vec<RecordedScore>,
+}
+
+#[ext_contract(ext_score_history)]
+trait ScoreHistory {
+    //query the whole score history of an account (panics if the account has none)
+    fn query_score_history(&self, account_id: String);
+}
+
+#[ext_contract(ext_score_bands)]
+trait ScoreBands {
+    //query the band table mapping score ranges to labels and tier ids
+    fn score_bands(&self);
+}
+
+//read the timestamp of the score (in nanoseconds) out of the 'extra' field of the token metadata,
+//either as a JSON number or as a string
+pub(crate) fn score_timestamp_from_extra(extra: &Option<String>) -> Option<u64> {
+    let extra: Value = near_sdk::serde_json::from_str(extra.as_ref()?).ok()?;
+    match extra.get("timestamp")? {
+        Value::String(timestamp) => timestamp.parse().ok(),
+        timestamp => timestamp.as_u64(),
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    //Query the storescore contract nft_mint() checks scores against, if any
+    pub fn score_contract(&self) -> Option<AccountId> {
+        self.score_contract_id.clone()
+    }
+
+    //Sets the storescore contract nft_mint() checks scores against.
+    //Passing None lets nft_mint() mint any score.
+    //This method can be called only by the smart contract owner.
+    pub fn set_score_contract(&mut self, score_contract_id: Option<AccountId>) {
+        //ensure the function was called by the smart contract owner, else panic
+        self.assert_called_by_owner();
+
+        self.score_contract_id = score_contract_id;
+    }
+
+    //Query the score bands labelling the on-chain score cards
+    pub fn score_bands(&self) -> Vec<ScoreBandEntry> {
+        self.score_bands.clone()
+    }
+
+    //Copies the band table of the score contract, so that the score cards
+    //are labelled the same way as the scores.
+    //This method can be called only by the smart contract owner.
+    pub fn sync_score_bands(&mut self) -> Promise {
+        //ensure the function was called by the smart contract owner, else panic
+        self.assert_called_by_owner();
+
+        let score_contract_id = self
+            .score_contract_id
+            .clone()
+            .expect("No score contract to sync the score bands from");
+        ext_score_bands::ext(score_contract_id)
+            .with_static_gas(GAS_FOR_QUERY_SCORE_BANDS)
+            .score_bands()
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_RESOLVE_SCORE_BANDS)
+                    .nft_resolve_score_bands(),
+            )
+    }
+
+    //resolves the cross contract call fetching the band table of the score contract:
+    //returns whether the score bands were updated
+    #[private]
+    pub fn nft_resolve_score_bands(&mut self) -> bool {
+        let bands = match env::promise_result(0) {
+            PromiseResult::Successful(value) => {
+                match near_sdk::serde_json::from_slice::<Vec<ScoreBandEntry>>(&value) {
+                    Ok(bands) => bands,
+                    Err(_) => return false,
+                }
+            }
+            _ => return false,
+        };
+        //keep the current bands rather than store a table that can't label every score
+        if bands.first().map_or(true, |band| band.min != 0)
+            || bands.windows(2).any(|pair| pair[0].min >= pair[1].min)
+        {
+            return false;
+        }
+        self.score_bands = bands;
+        true
+    }
+
+    //resolves the cross contract call fetching the receiver's score history in nft_mint():
+    //mints the token if the metadata matches the latest score, else refunds the deposit
+    #[private]
+    pub fn nft_resolve_score_check(&mut self, request: MintRequest, payer_id: AccountId, deposit: U128) -> MintOutcome {
+        let token_id = request.token_id.clone();
+        let receiver_id = request.receiver_id.clone();
+        match self.internal_score_checked_mint(request, &payer_id, deposit.0) {
+            Ok(outcome) => outcome,
+            Err(error) => fail_score_checked_mint(token_id, receiver_id, &payer_id, deposit.0, error),
+        }
+    }
+
+    //resolves the mint callback: if it failed, the deposit it was given is refunded
+    #[private]
+    pub fn nft_resolve_score_checked_mint(
+        &mut self,
+        token_id: TokenId,
+        receiver_id: AccountId,
+        payer_id: AccountId,
+        deposit: U128,
+    ) -> MintOutcome {
+        if let PromiseResult::Successful(value) = env::promise_result(0) {
+            if let Ok(outcome) = near_sdk::serde_json::from_slice::<MintOutcome>(&value) {
+                return outcome;
+            }
+        }
+        fail_score_checked_mint(
+            token_id,
+            receiver_id,
+            &payer_id,
+            deposit.0,
+            "Mint error: the score matched but the mint failed, e.g. the deposit didn't cover the storage".to_string(),
+        )
+    }
+}
+
+impl Contract {
+    //fetch the receiver's score history from the score contract, then mint in a callback.
+    //The mint is checked right away too, so that a mint bound to fail doesn't wait for the score.
+    pub(crate) fn internal_mint_with_score_check(&self, score_contract_id: AccountId, request: MintRequest) -> Promise {
+        let payer_id = env::predecessor_account_id();
+
+        //SCORE CHECK
+        if score_from_extra(&request.metadata.extra).is_none()
+            || score_timestamp_from_extra(&request.metadata.extra).is_none()
+        {
+            env::panic_str(
+                "Score check error: metadata.extra must hold the score and its timestamp, e.g. {\"score\": 712, \"timestamp\": 1650000000000000000}",
+            );
+        }
+
+        //LOGIC CHECKS
+        let prepared = self
+            .internal_prepare_mint(request.clone(), &payer_id)
+            .unwrap_or_else(|error| env::panic_str(&error));
+        self.internal_check_grant(&prepared).unwrap_or_else(|error| env::panic_str(&error));
+
+        //fetch the score history, mint in a callback, and refund the deposit if the callback fails
+        let token_id = request.token_id.clone();
+        let receiver_id = request.receiver_id.clone();
+        let deposit = U128(env::attached_deposit());
+        ext_score_history::ext(score_contract_id)
+            .with_static_gas(GAS_FOR_QUERY_SCORE_HISTORY)
+            .query_score_history(request.receiver_id.to_string())
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_RESOLVE_SCORE_CHECK)
+                    .nft_resolve_score_check(request, payer_id.clone(), deposit),
+            )
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_RESOLVE_SCORE_CHECKED_MINT)
+                    .nft_resolve_score_checked_mint(token_id, receiver_id, payer_id, deposit),
+            )
+    }
+
+    //check the metadata of a mint request against the latest score fetched from the score contract,
+    //then mint the token, paying the storage and the mint price out of the deposit
+    fn internal_score_checked_mint(
+        &mut self,
+        request: MintRequest,
+        payer_id: &AccountId,
+        deposit: Balance,
+    ) -> Result<MintOutcome, String> {
+        //measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        //SCORE CHECK
+        let history = match env::promise_result(0) {
+            PromiseResult::Successful(value) => near_sdk::serde_json::from_slice::<RecordedScoreHistory>(&value)
+                .map_err(|_| "Score check error: the score contract returned an invalid score history".to_string())?,
+            _ => return Err(format!("Score check error: {} has no score recorded", request.receiver_id)),
+        };
+        let latest = history
+            .scores
+            .last()
+            .ok_or_else(|| format!("Score check error: {} has no score recorded", request.receiver_id))?;
+        let score = score_from_extra(&request.metadata.extra);
+        let timestamp = score_timestamp_from_extra(&request.metadata.extra);
+        if score != Some(latest.score) || timestamp != Some(latest.timestamp) {
+            return Err(format!(
+                "Score check error: the metadata doesn't match the latest score of {}, which is {} recorded at {}",
+                request.receiver_id, latest.score, latest.timestamp
+            ));
+        }
+
+        //LOGIC CHECKS
+        //the state may have changed since nft_mint() was called, so the mint is checked again
+        let prepared = self.internal_prepare_mint(request, payer_id)?;
+        self.internal_check_grant(&prepared)?;
+        self.internal_use_grant();
+
+        //PAYMENT
+        let payment = self.internal_collect_mint_price(payer_id, &prepared.price);
+
+        //CORE
+        let (token_id, owner_id) = self.internal_mint(prepared);
+
+        //LOG EVENTS
+        log_nft_mint(vec![NftMintLog {
+            owner_id: owner_id.to_string(),
+            token_ids: vec![token_id.to_string()],
+            memo: None,
+        }]);
+
+        //PAYOUT
+        //refund the surplus of the deposit to the payer, or panic if it doesn't cover the storage and the mint price
+        let required_storage_in_bytes = env::storage_usage() - initial_storage_usage;
+        refund_deposit_of(payer_id, deposit, required_storage_in_bytes, payment);
+
+        //remove the whitelist grant of the caller if it's used up
+        self.internal_remove_used_grant();
+
+        Ok(MintOutcome {
+            gas_used: env::used_gas(),
+            nft_id: token_id,
+            owner_id,
+            successful_operation: true,
+            error: None,
+        })
+    }
+}
+
+//refund the whole deposit of a score-checked mint that failed and log why it failed
+fn fail_score_checked_mint(
+    token_id: TokenId,
+    receiver_id: AccountId,
+    payer_id: &AccountId,
+    deposit: Balance,
+    error: String,
+) -> MintOutcome {
+    if deposit > 0 {
+        Promise::new(payer_id.clone()).transfer(deposit);
+    }
+
+    //LOG EVENTS
+    let nft_mint_failure_log: EventLog = EventLog {
+        //standard name ("nearoracle")
+        standard: ORACLE_STANDARD_NAME.to_string(),
+        //version of the standard ("1.0.0")
+        version: ORACLE_EVENT_VERSION.to_string(),
+        //the data related with the event stored in a vector
+        event: EventLogVariant::NftMintFailure(vec![NftMintFailureLog {
+            owner_id: receiver_id.to_string(),
+            token_id: token_id.clone(),
+            reason: error.clone(),
+        }]),
+    };
+
+    //log the serialized json
+    env::log_str(&nft_mint_failure_log.to_string());
+
+    MintOutcome {
+        gas_used: env::used_gas(),
+        nft_id: token_id,
+        owner_id: receiver_id,
+        successful_operation: false,
+        error: Some(error),
+    }
+}
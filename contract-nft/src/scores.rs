@@ -0,0 +1,91 @@
+use crate::*;
+use near_sdk::ext_contract;
+use near_sdk::json_types::U64;
+
+/*
+Typed score data.
+The score of a token is read at mint from the 'extra' field of its metadata, e.g. {"score": 712},
+and stored apart from the free-form metadata along with its issue date, so that other contracts
+(e.g. lending pools) can check on chain whether an account holds a valid score NFT with a high
+enough score through nft_verify_score(). Tokens minted without a score simply never qualify.
+ */
+
+//the typed score carried by a token
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenScore {
+    //the numerical score
+    pub score: u16,
+    //when the score was issued (i.e., minted), Unix epoch in nanoseconds
+    pub issued_at: U64,
+}
+
+//the token backing a successful score verification, as returned by nft_verify_score()
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ScoreVerification {
+    pub token_id: TokenId,
+    pub score: u16,
+    //when the score was issued, Unix epoch in nanoseconds
+    pub issued_at: U64,
+}
+
+//interface for contracts verifying scores through cross contract calls
+#[ext_contract(ext_score_verifier)]
+pub trait ScoreVerifier {
+    //the most recent valid score NFT held by the account with a score of at least min_score,
+    //issued at most max_age nanoseconds ago (if given)
+    fn nft_verify_score(
+        &self,
+        account_id: AccountId,
+        min_score: u16,
+        max_age: Option<U64>,
+    ) -> Option<ScoreVerification>;
+}
+
+#[near_bindgen]
+impl ScoreVerifier for Contract {
+    //Query the most recent score NFT of the given account that is within its validity window,
+    //has a score of at least min_score and was issued at most max_age nanoseconds ago (if given).
+    //Returns None if the account holds no such token.
+    fn nft_verify_score(
+        &self,
+        account_id: AccountId,
+        min_score: u16,
+        max_age: Option<U64>,
+    ) -> Option<ScoreVerification> {
+        //if there isn't a set of tokens for the passed in account ID, nothing qualifies
+        let tokens_for_owner_set = self.tokens_per_owner.get(&account_id)?;
+
+        let now = env::block_timestamp();
+        //the oldest issue date still young enough, if a max age was given
+        let issued_after = max_age.map_or(0, |max_age| now.saturating_sub(max_age.0));
+
+        tokens_for_owner_set
+            .iter()
+            .filter_map(|token_id| {
+                let token_score = self.score_by_token.get(&token_id)?;
+                Some((token_id, token_score))
+            })
+            .filter(|(_, token_score)| token_score.score >= min_score && token_score.issued_at.0 >= issued_after)
+            .filter(|(token_id, _)| {
+                let metadata = self.token_metadata_by_id.get(token_id).unwrap();
+                token_is_valid(&metadata, now)
+            })
+            //the most recently issued score wins
+            .max_by_key(|(_, token_score)| token_score.issued_at.0)
+            .map(|(token_id, token_score)| ScoreVerification {
+                token_id,
+                score: token_score.score,
+                issued_at: token_score.issued_at,
+            })
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    //Query the typed score carried by the given token, if any
+    pub fn nft_token_score(&self, token_id: TokenId) -> Option<TokenScore> {
+        self.score_by_token.get(&token_id)
+    }
+}
@@ -0,0 +1,116 @@
+use crate::*;
+use near_sdk::serde_json::Value;
+
+/*
+On-chain score card media.
+When the owner switches on-chain media on, nft_mint() doesn't rely on an IPFS 'media' URI:
+it renders the score, its band, the mint date and the receiver account into an SVG image
+and stores it in the token metadata as a data URI, so that NEAR wallets can display the
+card with no external pinning service. The score is read from the token's 'extra' field,
+which must hold a JSON object such as {"score": 712}.
+ */
+
+//read the numerical score out of the 'extra' field of the token metadata
+pub(crate) fn score_from_extra(extra: &Option<String>) -> Option<u16> {
+    let extra: Value = near_sdk::serde_json::from_str(extra.as_ref()?).ok()?;
+    let score = extra.get("score")?.as_u64()?;
+    u16::try_from(score).ok()
+}
+
+//a score band of the storescore contract's band table: the band starts at 'min'
+//and ends where the next one starts
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ScoreBandEntry {
+    pub min: u16,
+    pub label: String,
+    pub tier: u8,
+}
+
+//the score bands used until the band table is synced from the storescore contract
+pub(crate) fn default_score_bands() -> Vec<ScoreBandEntry> {
+    [(0, "Very Poor"), (500, "Poor"), (580, "Fair"), (670, "Good"), (740, "Very Good"), (800, "Excellent")]
+        .iter()
+        .enumerate()
+        .map(|(index, (min, label))| ScoreBandEntry {
+            min: *min,
+            label: label.to_string(),
+            tier: index as u8 + 1,
+        })
+        .collect()
+}
+
+//map a numerical score to the label of its band
+pub(crate) fn score_band(bands: &[ScoreBandEntry], score: u16) -> &str {
+    bands
+        .iter()
+        .rev()
+        .find(|band| band.min <= score)
+        .map_or("", |band| band.label.as_str())
+}
+
+//format a nanosecond timestamp as a YYYY-MM-DD calendar date (UTC)
+pub(crate) fn format_date(timestamp: u64) -> String {
+    //days since 1970-01-01, converted to a civil date (Howard Hinnant's algorithm)
+    let days = (timestamp / NANOS_PER_DAY) as i64 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+//escape the characters that have a special meaning in XML
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+//render the score card as an SVG document
+pub(crate) fn render_score_card(score: u16, band: &str, account_id: &AccountId, timestamp: u64) -> String {
+    format!(
+        concat!(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" width="350" height="350" viewBox="0 0 350 350">"##,
+            r##"<rect width="350" height="350" rx="24" fill="#1d1d3b"/>"##,
+            r##"<text x="175" y="60" font-family="sans-serif" font-size="20" fill="#ffffff" text-anchor="middle">NearOracle Score</text>"##,
+            r##"<text x="175" y="170" font-family="sans-serif" font-size="84" font-weight="bold" fill="#ffffff" text-anchor="middle">{}</text>"##,
+            r##"<text x="175" y="215" font-family="sans-serif" font-size="24" fill="#7bd3ea" text-anchor="middle">{}</text>"##,
+            r##"<text x="175" y="280" font-family="sans-serif" font-size="16" fill="#c4c4d4" text-anchor="middle">{}</text>"##,
+            r##"<text x="175" y="310" font-family="sans-serif" font-size="14" fill="#c4c4d4" text-anchor="middle">{}</text>"##,
+            r##"</svg>"##
+        ),
+        score,
+        xml_escape(band),
+        format_date(timestamp),
+        xml_escape(account_id.as_str()),
+    )
+}
+
+//wrap an SVG document into a data URI that can be used as the token 'media'
+pub(crate) fn svg_data_uri(svg: &str) -> String {
+    format!("data:image/svg+xml;base64,{}", near_sdk::base64::encode(svg.as_bytes()))
+}
+
+#[near_bindgen]
+impl Contract {
+    //Query whether nft_mint() renders the score card media on chain
+    pub fn onchain_media(&self) -> bool {
+        self.onchain_media
+    }
+
+    //Switches the on-chain rendering of the score card media on or off.
+    //This method can be called only by the smart contract owner.
+    pub fn set_onchain_media(&mut self, enabled: bool) {
+        //ensure the function was called by the smart contract owner, else panic
+        self.assert_called_by_owner();
+
+        self.onchain_media = enabled;
+    }
+}
+
@@ -1,11 +1,18 @@
 /* unit tests */
 #[cfg(test)]
-use crate::Contract;
-use crate::{NFTContractMetadata, TokenMetadata};
+use crate::{Contract, NonFungibleTokenMetadata, OldContract, StorageKey, Token};
+use crate::{BatchMintMode, MintOutcome, MintPolicy, ScoreVerification, ScoreVerifier, TokenScore, MintPrice, MintRequest, MintVoucher, NFTContractMetadata, TokenMetadata, WhitelistGrant, NANOS_PER_DAY, NANOS_PER_SEC};
+use crate::svg::{format_date, render_score_card, svg_data_uri};
+use crate::approval::NonFungibleTokenCore as NonFungibleTokenApprovals;
+use crate::nft_core::{NonFungibleTokenCore, NonFungibleTokenResolver};
+use crate::royalty::NonFungibleTokenCore as NonFungibleTokenPayout;
 use near_sdk::testing_env;
-use near_sdk::json_types::{U128};
-use near_sdk::{env, AccountId, VMContext};
-use near_sdk::test_utils::{VMContextBuilder};
+use near_sdk::json_types::{Base64VecU8, U128, U64};
+use near_sdk::borsh::BorshSerialize;
+use near_sdk::collections::{LazyOption, LookupMap, LookupSet, UnorderedMap, UnorderedSet};
+use near_sdk::{env, AccountId, PromiseOrValue, PromiseResult, PublicKey, RuntimeFeesConfig, VMConfig, VMContext};
+use ed25519_dalek::{Keypair, SecretKey, Signer};
+use near_sdk::test_utils::{get_logs, VMContextBuilder};
 
 fn account(account_name: &str) -> AccountId {
     account_name.to_string().try_into().unwrap()
@@ -26,7 +33,8 @@ fn meta(mymedia: &str) -> TokenMetadata {
         title: "Test NFT".to_string(),
         description: "A minted NFT".to_string(),
         media: [mymedia, ".png"].join("").to_string(),
-        media_hash: None,
+        //stand-in for the sha256 hash of the media content
+        media_hash: Some(Base64VecU8(env::sha256(mymedia.as_bytes()))),
         copies: None,
         issued_at: Some(env::block_timestamp()),
         expires_at: None,
@@ -38,6 +46,25 @@ fn meta(mymedia: &str) -> TokenMetadata {
     }
 }
 
+//construct a mint policy without cooldown, so that tests can mint consecutively
+fn no_cooldown_policy() -> MintPolicy {
+    MintPolicy {
+        cooldown: U64(0),
+        ..MintPolicy::default()
+    }
+}
+
+//construct a single-use whitelist grant to mint to the given receiver
+fn grant(receiver: &str) -> WhitelistGrant {
+    WhitelistGrant {
+        receiver_id: account(receiver),
+        media_hash: None,
+        uses: 1,
+        expires_at: None,
+        reason: "test".to_string(),
+    }
+}
+
 //unit tests start here
 #[test]
 fn test_whitelist() {
@@ -45,7 +72,7 @@ fn test_whitelist() {
     let mut context = get_context( 
         account("doomslug.testnet")
     );
-    testing_env!(context);
+    testing_env!(context.clone());
     let mut contract = Contract::new_default_meta(
         account("doomslug.testnet")
     );
@@ -55,17 +82,19 @@ fn test_whitelist() {
     assert_eq!("doomslug.testnet".to_string(), String::from(env::predecessor_account_id()));
     let a = account("rainbow.testnet");
 
-    //Adding to list by foundation
-    assert!(contract.add_to_whitelist(&a));
+    //Adding to list by foundation, paying for the storage of the grant
+    context.attached_deposit = u128::pow(10, 23);
+    testing_env!(context.clone());
+    assert!(contract.add_to_whitelist(&a, grant("rainbow.testnet")));
 
     //Checking it's whitelisted now
-    assert!(contract.whitelist.contains(&a));
+    assert!(contract.is_whitelisted(a.clone()));
 
     //Adding again. Should return false
-    assert!(!contract.add_to_whitelist(&a));
+    assert!(!contract.add_to_whitelist(&a, grant("rainbow.testnet")));
 
     //Checking it's still whitelisted
-    assert!(contract.whitelist.contains(&a));
+    assert!(contract.is_whitelisted(a.clone()));
 }
 
 
@@ -84,7 +113,7 @@ fn test_whitelist_fail() {
     //try ot add to the whitelist by a NOT whitelisted account id -> should panic
     context.predecessor_account_id = account("benji.testnet");
     testing_env!(context.clone());
-    assert!(contract.add_to_whitelist(&account("rainbow.testnet")));
+    assert!(contract.add_to_whitelist(&account("rainbow.testnet"), grant("rainbow.testnet")));
 }
 
 
@@ -102,7 +131,7 @@ fn test_init() {
         symbol: "Balloonbox".to_string(),
         timestamp: env::block_timestamp(),
         icon: None,
-        base_uri: None,
+        base_uri: "https://ipfs.io/ipfs/".to_string(),
         reference: None,
         reference_hash: None,
     };
@@ -136,6 +165,7 @@ fn test_enums() {
     let mut contract = Contract::new_default_meta(
         account("doomslug.testnet")
     );
+    contract.set_mint_policy(no_cooldown_policy());
 
     //declare account ids
     let s = account("spensa.testnet");
@@ -150,6 +180,7 @@ fn test_enums() {
         "001".to_string(), 
         meta("nft#1"),
         s, 
+        None,
         None
     );
 
@@ -160,6 +191,7 @@ fn test_enums() {
         "002".to_string(),
         meta("nft#2"), 
         s1, 
+        None,
         None
     );
 
@@ -170,6 +202,7 @@ fn test_enums() {
         "003".to_string(),
         meta("nft#3"),
         r,
+        None,
         None
     );
 
@@ -201,6 +234,7 @@ pub fn test_mint() {
         "001".to_string(),
         meta("nft#1"), 
         b,
+        None,
         None
     );
 
@@ -221,17 +255,22 @@ pub fn test_mint() {
 
     //mint second NFT attaching a deposit
     let spensa = account("spensa.testnet");
-    contract.add_to_whitelist(&spensa);
+    contract.add_to_whitelist(&spensa, grant("spensa.testnet"));
     context.signer_account_id = spensa.clone();
     testing_env!(context.clone());
 
-    let token2 = contract.nft_mint(
+    let token2 = match contract.nft_mint(
         "002".to_string(),
         meta("nft#2"), 
         spensa.clone(),
+        None,
         None
-    );
-    assert!(!contract.whitelist.contains(&spensa));
+    ) {
+        PromiseOrValue::Value(outcome) => outcome,
+        PromiseOrValue::Promise(_) => panic!("nft_mint() should mint right away"),
+    };
+    //the single-use grant was consumed
+    assert!(contract.whitelist_grant(spensa.clone()).is_none());
     assert!(token2.successful_operation);
     assert_eq!("002".to_string(), token2.nft_id);
 }
@@ -249,6 +288,7 @@ pub fn test_mint_duplicate() {
         account("doomslug.testnet")
     );
     let s = account("spensa.testnet");
+    contract.set_mint_policy(no_cooldown_policy());
 
 
     //try mint the same NFT twice
@@ -258,6 +298,7 @@ pub fn test_mint_duplicate() {
         "001".to_string(),
         meta("duplicate-nft"), 
         s.clone(),
+        None,
         None
     );
     context.attached_deposit = u128::pow(10, 23);
@@ -266,6 +307,7 @@ pub fn test_mint_duplicate() {
         "002".to_string(),
         meta("duplicate-nft"), 
         s.clone(),
+        None,
         None
     );
 }
@@ -291,6 +333,1610 @@ pub fn test_mint_permissionless() {
         "001".to_string(),
         meta("nft#1"), 
         s.clone(),
+        None,
+        None
+    );
+}
+
+
+#[test]
+pub fn test_token_validity() {
+    //set up the testing context
+    let mut context = get_context(
+        account("doomslug.testnet")
+    );
+    testing_env!(context.clone());
+    let mut contract = Contract::new_default_meta(
+        account("doomslug.testnet")
+    );
+    let s = account("spensa.testnet");
+
+    //tokens minted from now on expire after 1 hour
+    let lifetime = 3600 * u64::pow(10, 9);
+    contract.set_default_token_lifetime(Some(U64(lifetime)));
+
+    //mint an NFT attaching a deposit
+    context.attached_deposit = u128::pow(10, 23);
+    testing_env!(context.clone());
+    contract.nft_mint(
+        "001".to_string(),
+        meta("nft#1"),
+        s.clone(),
+        None,
+        None
+    );
+
+    //the token is valid right after minting
+    assert!(contract.nft_is_valid("001".to_string()));
+    assert_eq!(U128(1), contract.nft_valid_supply_for_owner(&s));
+    assert_eq!(1, contract.nft_tokens_for_owner(&s, None, None, Some(true)).len());
+
+    //move past the expiry: the token is still owned but no longer valid
+    context.block_timestamp = lifetime + 1;
+    testing_env!(context.clone());
+    assert!(!contract.nft_is_valid("001".to_string()));
+    assert_eq!(U128(0), contract.nft_valid_supply_for_owner(&s));
+    assert_eq!(U128(1), contract.nft_supply_for_owner(&s));
+    assert_eq!(0, contract.nft_tokens_for_owner(&s, None, None, Some(true)).len());
+    assert_eq!(1, contract.nft_tokens_for_owner(&s, None, None, None).len());
+}
+
+
+#[test]
+pub fn test_timestamps_json() {
+    //timestamps are stored in nanoseconds...
+    let mut metadata = meta("nft#1");
+    metadata.issued_at = Some(1_650_000_000_123_456_789);
+    metadata.expires_at = None;
+
+    //...and serialized as NEP-177 millisecond strings
+    let json = near_sdk::serde_json::to_value(&metadata).unwrap();
+    assert_eq!("1650000000123", json["issued_at"]);
+    assert!(json["expires_at"].is_null());
+
+    //millisecond strings are read back as nanoseconds
+    let parsed: TokenMetadata = near_sdk::serde_json::from_str(
+        r#"{"title": "t", "description": "d", "media": "m", "issued_at": "1650000000123"}"#
+    ).unwrap();
+    assert_eq!(Some(1_650_000_000_123_000_000), parsed.issued_at);
+    assert_eq!(None, parsed.updated_at);
+}
+
+
+#[test]
+pub fn test_migrate_token_timestamps() {
+    //set up the testing context
+    let context = get_context(
+        account("doomslug.testnet")
+    );
+    testing_env!(context);
+    let mut contract = Contract::new_default_meta(
+        account("doomslug.testnet")
+    );
+
+    //store a legacy token whose expiry was written in milliseconds
+    let mut legacy = meta("nft#1");
+    legacy.issued_at = Some(1_650_000_000_000_000_000);
+    legacy.expires_at = Some(1_700_000_000_000);
+    contract.token_metadata_by_id.insert(&"001".to_string(), &legacy);
+
+    //only the millisecond timestamp gets converted
+    assert_eq!(1, contract.migrate_token_timestamps(None, None));
+    let migrated = contract.token_metadata_by_id.get(&"001".to_string()).unwrap();
+    assert_eq!(Some(1_650_000_000_000_000_000), migrated.issued_at);
+    assert_eq!(Some(1_700_000_000_000_000_000), migrated.expires_at);
+
+    //running the migration again is a no-op
+    assert_eq!(0, contract.migrate_token_timestamps(None, None));
+}
+
+//write the state of the original version of the contract, holding one legacy token
+//minted to rainbow.testnet with its timestamps in milliseconds
+fn write_legacy_state() {
+    let mut tokens_per_owner = LookupMap::new(StorageKey::TokensPerOwner.try_to_vec().unwrap());
+    let mut token_by_id = LookupMap::new(StorageKey::TokensById.try_to_vec().unwrap());
+    let mut token_metadata_by_id = UnorderedMap::new(StorageKey::TokenMetadataById.try_to_vec().unwrap());
+    let mut whitelist = LookupSet::new(StorageKey::WhiteList.try_to_vec().unwrap());
+    whitelist.insert(&account("rainbow.testnet"));
+
+    let token_id = "legacy".to_string();
+    let mut tokens = UnorderedSet::new(b"legacy_owner".to_vec());
+    tokens.insert(&token_id);
+    tokens_per_owner.insert(&account("rainbow.testnet"), &tokens);
+    token_by_id.insert(&token_id, &Token {
+        owner_id: account("rainbow.testnet"),
+        approved_account_ids: Default::default(),
+        next_approval_id: 0,
+        royalty: Default::default(),
+    });
+    let mut legacy = meta("legacy");
+    legacy.issued_at = Some(1_650_000_000_000);
+    legacy.extra = Some("{\"score\": 720}".to_string());
+    token_metadata_by_id.insert(&token_id, &legacy);
+
+    let metadata = NFTContractMetadata {
+        spec: "nft-1.0.0".to_string(),
+        name: "nft_minter".to_string(),
+        symbol: "BBOXNFTMINT".to_string(),
+        timestamp: 0,
+        icon: None,
+        base_uri: "https://ipfs.io/ipfs/".to_string(),
+        reference: None,
+        reference_hash: None,
+    };
+    env::state_write(&OldContract {
+        owner_id: account("doomslug.testnet"),
+        tokens_per_owner,
+        token_by_id,
+        token_metadata_by_id,
+        metadata: LazyOption::new(StorageKey::NFTContractMetadata.try_to_vec().unwrap(), Some(&metadata)),
+        whitelist,
+    });
+}
+
+#[test]
+pub fn test_migrate() {
+    //the contract account itself runs the migration when redeploying
+    let mut context = get_context(account("spensa.testnet"));
+    testing_env!(context.clone());
+    write_legacy_state();
+    let mut contract = Contract::migrate();
+
+    //tokens and metadata are kept, new fields get their defaults
+    assert_eq!(account("doomslug.testnet"), contract.owner_id);
+    assert_eq!("nft_minter", contract.nft_metadata().name);
+    let tokens = contract.nft_tokens_for_owner(&account("rainbow.testnet"), None, None, None);
+    assert_eq!(1, tokens.len());
+    assert_eq!("legacy", tokens[0].token_id);
+    assert_eq!(1, contract.nft_total_supply().0);
+    assert!(!contract.is_whitelisted(account("rainbow.testnet")));
+    assert_eq!(None, contract.score_contract_id);
+
+    //the owner then converts the legacy timestamps
+    context.predecessor_account_id = account("doomslug.testnet");
+    testing_env!(context);
+    assert_eq!(1, contract.migrate_token_timestamps(None, None));
+    let metadata = contract.token_metadata_by_id.get(&"legacy".to_string()).unwrap();
+    assert_eq!(Some(1_650_000_000_000_000_000), metadata.issued_at);
+}
+
+#[test]
+pub fn test_index_minted_tokens_after_migrate() {
+    let mut context = get_context(account("spensa.testnet"));
+    testing_env!(context.clone());
+    write_legacy_state();
+    let mut contract = Contract::migrate();
+
+    //the legacy token isn't known to the mint indexes yet
+    assert_eq!(None, contract.nft_last_minted_at(account("rainbow.testnet")));
+
+    //the owner converts the timestamps, then indexes the legacy tokens
+    context.predecessor_account_id = account("doomslug.testnet");
+    testing_env!(context);
+    contract.migrate_token_timestamps(None, None);
+    contract.index_minted_tokens(None, None);
+    assert_eq!(Some(U64(1_650_000_000_000_000_000)), contract.nft_last_minted_at(account("rainbow.testnet")));
+    assert_eq!(
+        Some("legacy".to_string()),
+        contract.nft_token_by_media_hash(Base64VecU8(env::sha256(b"legacy")))
+    );
+    assert_eq!(720, contract.score_by_token.get(&"legacy".to_string()).unwrap().score);
+}
+
+
+
+#[test]
+pub fn test_onchain_media() {
+    //date formatting and base64 encoding used by the score card
+    assert_eq!("1970-01-01", format_date(0));
+    assert_eq!("2022-04-15", format_date(1_650_000_000_000_000_000));
+    assert_eq!("data:image/svg+xml;base64,TWFu", svg_data_uri("Man"));
+    assert_eq!("data:image/svg+xml;base64,TWE=", svg_data_uri("Ma"));
+
+    //set up the testing context
+    let mut context = get_context(
+        account("doomslug.testnet")
+    );
+    testing_env!(context.clone());
+    let mut contract = Contract::new_default_meta(
+        account("doomslug.testnet")
+    );
+    contract.set_onchain_media(true);
+
+    //mint an NFT whose score is carried by the extra field
+    let mut metadata = meta("unused");
+    metadata.extra = Some("{\"score\": 712}".to_string());
+    context.attached_deposit = u128::pow(10, 23);
+    testing_env!(context.clone());
+    contract.nft_mint(
+        "001".to_string(),
+        metadata,
+        account("spensa.testnet"),
+        None,
+        None
+    );
+
+    //the media is an SVG data URI whose hash was computed over the rendered image
+    let minted = contract.token_metadata_by_id.get(&"001".to_string()).unwrap();
+    assert!(minted.media.starts_with("data:image/svg+xml;base64,"));
+    assert_eq!(32, minted.media_hash.unwrap().0.len());
+}
+
+#[test]
+fn test_sync_score_bands() {
+    //set up the testing context
+    let mut context = get_context(
+        account("doomslug.testnet")
+    );
+    testing_env!(context.clone());
+    let mut contract = Contract::new_default_meta(
+        account("doomslug.testnet")
+    );
+    contract.set_onchain_media(true);
+    assert_eq!("Good", contract.score_bands()[3].label);
+
+    //the callback stores the band table returned by the score contract, if it labels every score
+    context.predecessor_account_id = account("spensa.testnet");
+    for (table, synced) in [
+        (&b"[{\"min\": 300, \"label\": \"Poor\", \"tier\": 1}]"[..], false),
+        (&b"[{\"min\": 0, \"label\": \"Subprime\", \"tier\": 1}, {\"min\": 620, \"label\": \"Prime\", \"tier\": 2}]"[..], true),
+    ] {
+        testing_env!(
+            context.clone(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(table.to_vec())]
+        );
+        assert_eq!(synced, contract.nft_resolve_score_bands());
+    }
+    assert_eq!(2, contract.score_bands().len());
+
+    //the score cards are labelled with the synced bands
+    let mut metadata = meta("unused");
+    metadata.extra = Some("{\"score\": 712}".to_string());
+    context.predecessor_account_id = account("doomslug.testnet");
+    context.attached_deposit = u128::pow(10, 23);
+    testing_env!(context.clone());
+    contract.nft_mint("001".to_string(), metadata, account("spensa.testnet"), None, None);
+    let minted = contract.token_metadata_by_id.get(&"001".to_string()).unwrap();
+    let svg = render_score_card(712, "Prime", &account("spensa.testnet"), env::block_timestamp());
+    assert_eq!(svg_data_uri(&svg), minted.media);
+}
+
+
+
+#[test]
+#[should_panic(expected = "Hash error: media_hash is required")]
+pub fn test_mint_without_media_hash() {
+    //set up the testing context
+    let mut context = get_context(
+        account("doomslug.testnet")
+    );
+    testing_env!(context.clone());
+    let mut contract = Contract::new_default_meta(
+        account("doomslug.testnet")
+    );
+
+    //try to mint an NFT without the hash of its media content
+    let mut metadata = meta("nft#1");
+    metadata.media_hash = None;
+    context.attached_deposit = u128::pow(10, 23);
+    testing_env!(context.clone());
+    contract.nft_mint(
+        "001".to_string(),
+        metadata,
+        account("spensa.testnet"),
+        None,
+        None
+    );
+}
+
+
+#[test]
+#[should_panic(expected = "Hash error: reference_hash is required when reference is set")]
+pub fn test_mint_reference_without_hash() {
+    //set up the testing context
+    let mut context = get_context(
+        account("doomslug.testnet")
+    );
+    testing_env!(context.clone());
+    let mut contract = Contract::new_default_meta(
+        account("doomslug.testnet")
+    );
+
+    //try to mint an NFT with a reference but no reference_hash
+    let mut metadata = meta("nft#1");
+    metadata.reference = Some("https://ipfs.io/ipfs/reference.json".to_string());
+    context.attached_deposit = u128::pow(10, 23);
+    testing_env!(context.clone());
+    contract.nft_mint(
+        "001".to_string(),
+        metadata,
+        account("spensa.testnet"),
+        None,
+        None
+    );
+}
+
+
+#[test]
+#[should_panic(expected = "Duplicate error: you can't mint the same NFT twice")]
+pub fn test_mint_duplicate_content() {
+    //set up the testing context
+    let mut context = get_context(
+        account("doomslug.testnet")
+    );
+    testing_env!(context.clone());
+    let mut contract = Contract::new_default_meta(
+        account("doomslug.testnet")
+    );
+    let s = account("spensa.testnet");
+
+    context.attached_deposit = u128::pow(10, 23);
+    testing_env!(context.clone());
+    contract.nft_mint(
+        "001".to_string(),
+        meta("score"),
+        s.clone(),
+        None,
+        None
+    );
+
+    //a month later, mint a different URI pointing at the same content
+    let mut metadata = meta("score");
+    metadata.media = "https://another.gateway/score.png".to_string();
+    context.block_timestamp = 31 * 86_400 * u64::pow(10, 9);
+    testing_env!(context.clone());
+    contract.nft_mint(
+        "002".to_string(),
+        metadata,
+        s.clone(),
+        None,
         None
     );
-}
\ No newline at end of file
+}
+
+
+#[test]
+#[should_panic(expected = "Duplicate error: this NFT was already minted on the contract")]
+pub fn test_mint_duplicate_contract_wide() {
+    //set up the testing context
+    let mut context = get_context(
+        account("doomslug.testnet")
+    );
+    testing_env!(context.clone());
+    let mut contract = Contract::new_default_meta(
+        account("doomslug.testnet")
+    );
+    contract.set_unique_media_contract_wide(true);
+
+    //mint the same content to two different receivers
+    context.attached_deposit = u128::pow(10, 23);
+    testing_env!(context.clone());
+    contract.nft_mint(
+        "001".to_string(),
+        meta("score"),
+        account("spensa.testnet"),
+        None,
+        None
+    );
+    assert_eq!(Some("001".to_string()), contract.nft_token_by_media_hash(meta("score").media_hash.unwrap()));
+    assert!(contract.nft_last_minted_at(account("spensa.testnet")).is_some());
+    contract.nft_mint(
+        "002".to_string(),
+        meta("score"),
+        account("rainbow.testnet"),
+        None,
+        None
+    );
+}
+
+
+
+#[test]
+pub fn test_mint_policy() {
+    //set up the testing context
+    let context = get_context(
+        account("doomslug.testnet")
+    );
+    testing_env!(context);
+    let mut contract = Contract::new_default_meta(
+        account("doomslug.testnet")
+    );
+
+    //the contract starts with the default policy
+    assert_eq!(12, contract.mint_policy().max_tokens_per_user);
+    assert_eq!(Some(100), contract.mint_policy().max_total_supply);
+
+    //lift the global cap and allow a single NFT per user
+    contract.set_mint_policy(MintPolicy {
+        max_tokens_per_user: 1,
+        max_total_supply: None,
+        cooldown: U64(0),
+    });
+    assert_eq!(None, contract.mint_policy().max_total_supply);
+    assert_eq!(1, contract.mint_policy().max_tokens_per_user);
+}
+
+
+#[test]
+#[should_panic(expected = "You can mint a limited amount of NFTs per user. You exceeded that limit")]
+pub fn test_mint_policy_user_cap() {
+    //set up the testing context
+    let mut context = get_context(
+        account("doomslug.testnet")
+    );
+    testing_env!(context.clone());
+    let mut contract = Contract::new_default_meta(
+        account("doomslug.testnet")
+    );
+    contract.set_mint_policy(MintPolicy {
+        max_tokens_per_user: 1,
+        ..no_cooldown_policy()
+    });
+
+    //the second NFT exceeds the per-user cap
+    context.attached_deposit = u128::pow(10, 23);
+    testing_env!(context.clone());
+    contract.nft_mint(
+        "001".to_string(),
+        meta("nft#1"),
+        account("spensa.testnet"),
+        None,
+        None
+    );
+    contract.nft_mint(
+        "002".to_string(),
+        meta("nft#2"),
+        account("spensa.testnet"),
+        None,
+        None
+    );
+}
+
+
+//construct a sample MintRequest struct
+fn mint_request(token_id: &str, mymedia: &str, receiver: &str) -> MintRequest {
+    MintRequest {
+        token_id: token_id.to_string(),
+        metadata: meta(mymedia),
+        receiver_id: account(receiver),
+        perpetual_royalties: None,
+        token_type: None,
+    }
+}
+
+
+#[test]
+pub fn test_batch_mint() {
+    //set up the testing context
+    let mut context = get_context(
+        account("doomslug.testnet")
+    );
+    testing_env!(context.clone());
+    let mut contract = Contract::new_default_meta(
+        account("doomslug.testnet")
+    );
+    contract.set_mint_policy(no_cooldown_policy());
+
+    //mint 3 NFTs to 2 owners in a single transaction
+    context.attached_deposit = u128::pow(10, 24);
+    testing_env!(context.clone());
+    let outcomes = contract.nft_batch_mint(
+        vec![
+            mint_request("001", "nft#1", "spensa.testnet"),
+            mint_request("002", "nft#2", "rainbow.testnet"),
+            mint_request("003", "nft#3", "spensa.testnet"),
+        ],
+        None
+    );
+    assert_eq!(3, outcomes.len());
+    assert!(outcomes.iter().all(|outcome| outcome.successful_operation));
+    assert_eq!(U128(3), contract.nft_total_supply());
+    assert_eq!(U128(2), contract.nft_supply_for_owner(&account("spensa.testnet")));
+
+    //a single nft_mint event groups the token IDs per owner
+    let mint_events: Vec<String> = get_logs()
+        .into_iter()
+        .filter(|log| log.contains("\"event\":\"nft_mint\""))
+        .collect();
+    assert_eq!(1, mint_events.len());
+    assert!(mint_events[0].contains("{\"owner_id\":\"spensa.testnet\",\"token_ids\":[\"001\",\"003\"]}"));
+}
+
+
+#[test]
+pub fn test_batch_mint_best_effort() {
+    //set up the testing context
+    let mut context = get_context(
+        account("doomslug.testnet")
+    );
+    testing_env!(context.clone());
+    let mut contract = Contract::new_default_meta(
+        account("doomslug.testnet")
+    );
+    contract.set_mint_policy(no_cooldown_policy());
+
+    //the second request reuses a token ID and gets skipped
+    context.attached_deposit = u128::pow(10, 24);
+    testing_env!(context.clone());
+    let outcomes = contract.nft_batch_mint(
+        vec![
+            mint_request("001", "nft#1", "spensa.testnet"),
+            mint_request("001", "nft#2", "rainbow.testnet"),
+            mint_request("002", "nft#3", "rainbow.testnet"),
+        ],
+        Some(BatchMintMode::BestEffort)
+    );
+    assert!(outcomes[0].successful_operation);
+    assert!(!outcomes[1].successful_operation);
+    assert_eq!(Some("Token already exists".to_string()), outcomes[1].error);
+    assert!(outcomes[2].successful_operation);
+    assert_eq!(U128(2), contract.nft_total_supply());
+}
+
+
+#[test]
+#[should_panic(expected = "Batch mint failed on token 001: Token already exists")]
+pub fn test_batch_mint_all_or_nothing() {
+    //set up the testing context
+    let mut context = get_context(
+        account("doomslug.testnet")
+    );
+    testing_env!(context.clone());
+    let mut contract = Contract::new_default_meta(
+        account("doomslug.testnet")
+    );
+    contract.set_mint_policy(no_cooldown_policy());
+
+    //the second request reuses a token ID and fails the whole batch
+    context.attached_deposit = u128::pow(10, 24);
+    testing_env!(context.clone());
+    contract.nft_batch_mint(
+        vec![
+            mint_request("001", "nft#1", "spensa.testnet"),
+            mint_request("001", "nft#2", "rainbow.testnet"),
+        ],
+        None
+    );
+}
+
+
+#[test]
+pub fn test_batch_mint_best_effort_deposit() {
+    //set up the testing context
+    let mut context = get_context(
+        account("doomslug.testnet")
+    );
+    testing_env!(context.clone());
+    let mut contract = Contract::new_default_meta(
+        account("doomslug.testnet")
+    );
+    contract.set_mint_policy(no_cooldown_policy());
+    contract.set_mint_price(
+        Some("premium".to_string()),
+        Some(MintPrice { amount: U128(ONE_NEAR / 2), ft_contract_id: None })
+    );
+
+    //the deposit covers the first premium token, but not a second one on top of it
+    context.attached_deposit = ONE_NEAR;
+    testing_env!(context.clone());
+    let premium = |token_id: &str, mymedia: &str, receiver: &str| MintRequest {
+        token_type: Some("premium".to_string()),
+        ..mint_request(token_id, mymedia, receiver)
+    };
+    let outcomes = contract.nft_batch_mint(
+        vec![
+            premium("001", "nft#1", "spensa.testnet"),
+            premium("002", "nft#2", "rainbow.testnet"),
+            mint_request("003", "nft#3", "spensa.testnet"),
+        ],
+        Some(BatchMintMode::BestEffort)
+    );
+    assert!(outcomes[0].successful_operation);
+    assert!(!outcomes[1].successful_operation);
+    assert!(outcomes[1].error.as_ref().unwrap().starts_with("Payment error: the batch requires"));
+    assert!(outcomes[2].successful_operation);
+
+    //the skipped token left nothing behind, and its price wasn't collected
+    assert_eq!(U128(2), contract.nft_total_supply());
+    assert!(contract.whose_token("002".to_string()).is_none());
+    assert!(contract.nft_last_minted_at(account("rainbow.testnet")).is_none());
+    assert!(contract.nft_token_by_media_hash(Base64VecU8(env::sha256(b"nft#2"))).is_none());
+    assert_eq!(contract.treasury_revenue()[0].collected, U128(ONE_NEAR / 2));
+}
+
+//ed25519 keypair standing in for the oracle backend signing mint vouchers
+fn voucher_keypair() -> Keypair {
+    let secret = SecretKey::from_bytes(&[7; 32]).unwrap();
+    let public = (&secret).into();
+    Keypair { secret, public }
+}
+
+//construct a mint voucher for the given metadata, signed by the voucher keypair
+fn signed_voucher(token_id: &str, metadata: &TokenMetadata, receiver: &str, nonce: u64) -> (MintVoucher, Base64VecU8) {
+    let voucher = MintVoucher {
+        receiver_id: account(receiver),
+        token_id: token_id.to_string(),
+        metadata_hash: Base64VecU8(env::sha256(&metadata.try_to_vec().unwrap())),
+        expires_at: U64(env::block_timestamp() + 3_600 * NANOS_PER_SEC),
+        nonce: U64(nonce),
+        token_type: None,
+    };
+    let message = (env::current_account_id(), &voucher).try_to_vec().unwrap();
+    let signature = voucher_keypair().sign(&message).to_bytes().to_vec();
+    (voucher, Base64VecU8(signature))
+}
+
+//set up a contract whose voucher signer is the voucher keypair
+fn voucher_contract(context: &VMContext) -> Contract {
+    testing_env!(context.clone());
+    let mut contract = Contract::new_default_meta(
+        account("doomslug.testnet")
+    );
+    contract.set_mint_policy(no_cooldown_policy());
+    let mut public_key = vec![0];
+    public_key.extend_from_slice(voucher_keypair().public.as_bytes());
+    contract.set_voucher_signer(Some(PublicKey::try_from(public_key).unwrap()));
+    contract
+}
+
+
+#[test]
+pub fn test_mint_with_voucher() {
+    //set up the testing context
+    let mut context = get_context(
+        account("doomslug.testnet")
+    );
+    let mut contract = voucher_contract(&context);
+    let metadata = meta("nft#1");
+    let (voucher, signature) = signed_voucher("001", &metadata, "rainbow.testnet", 1);
+
+    //the receiver redeems the voucher without being whitelisted
+    context.predecessor_account_id = account("rainbow.testnet");
+    context.attached_deposit = u128::pow(10, 24);
+    testing_env!(context.clone());
+    assert!(!contract.is_whitelisted(account("rainbow.testnet")));
+    let outcome = contract.nft_mint_with_voucher(voucher, signature, metadata, None);
+    assert!(outcome.successful_operation);
+    assert_eq!(outcome.owner_id, account("rainbow.testnet"));
+    assert_eq!(contract.nft_total_supply(), U128(1));
+    assert!(contract.is_voucher_redeemed(U64(1)));
+}
+
+
+#[test]
+#[should_panic(expected = "Voucher minting is unavailable while scores are checked against storescore.testnet")]
+pub fn test_mint_with_voucher_score_checked() {
+    //set up the testing context
+    let mut context = get_context(
+        account("doomslug.testnet")
+    );
+    let mut contract = voucher_contract(&context);
+    contract.set_score_contract(Some(account("storescore.testnet")));
+    let metadata = meta("nft#1");
+    let (voucher, signature) = signed_voucher("001", &metadata, "rainbow.testnet", 1);
+
+    //a voucher would skip the score check that nft_mint() runs
+    context.predecessor_account_id = account("rainbow.testnet");
+    context.attached_deposit = u128::pow(10, 24);
+    testing_env!(context.clone());
+    contract.nft_mint_with_voucher(voucher, signature, metadata, None);
+}
+
+#[test]
+#[should_panic(expected = "Voucher error: the voucher was already redeemed")]
+pub fn test_mint_with_voucher_replay() {
+    //set up the testing context
+    let mut context = get_context(
+        account("doomslug.testnet")
+    );
+    let mut contract = voucher_contract(&context);
+    let (voucher, signature) = signed_voucher("001", &meta("nft#1"), "rainbow.testnet", 1);
+    let (replayed_voucher, replayed_signature) = signed_voucher("001", &meta("nft#1"), "rainbow.testnet", 1);
+
+    //redeeming the same voucher twice fails
+    context.predecessor_account_id = account("rainbow.testnet");
+    context.attached_deposit = u128::pow(10, 24);
+    testing_env!(context.clone());
+    contract.nft_mint_with_voucher(voucher, signature, meta("nft#1"), None);
+    contract.nft_mint_with_voucher(replayed_voucher, replayed_signature, meta("nft#1"), None);
+}
+
+
+#[test]
+#[should_panic(expected = "Voucher error: the metadata doesn't match the voucher")]
+pub fn test_mint_with_voucher_tampered_metadata() {
+    //set up the testing context
+    let mut context = get_context(
+        account("doomslug.testnet")
+    );
+    let mut contract = voucher_contract(&context);
+    let (voucher, signature) = signed_voucher("001", &meta("nft#1"), "rainbow.testnet", 1);
+
+    //the receiver can't swap the signed metadata for another one
+    context.predecessor_account_id = account("rainbow.testnet");
+    context.attached_deposit = u128::pow(10, 24);
+    testing_env!(context.clone());
+    contract.nft_mint_with_voucher(voucher, signature, meta("nft#2"), None);
+}
+
+
+#[test]
+#[should_panic(expected = "Voucher error: only the receiver of the voucher can redeem it")]
+pub fn test_mint_with_voucher_wrong_receiver() {
+    //set up the testing context
+    let mut context = get_context(
+        account("doomslug.testnet")
+    );
+    let mut contract = voucher_contract(&context);
+    let metadata = meta("nft#1");
+    let (voucher, signature) = signed_voucher("001", &metadata, "rainbow.testnet", 1);
+
+    //another account can't redeem a voucher issued to the receiver
+    context.predecessor_account_id = account("spensa.testnet");
+    context.attached_deposit = u128::pow(10, 24);
+    testing_env!(context.clone());
+    contract.nft_mint_with_voucher(voucher, signature, metadata, None);
+}
+
+
+//1 NEAR, in yoctoNEAR
+const ONE_NEAR: u128 = 1_000_000_000_000_000_000_000_000;
+
+
+#[test]
+pub fn test_paid_mint() {
+    //set up the testing context
+    let mut context = get_context(
+        account("doomslug.testnet")
+    );
+    testing_env!(context.clone());
+    let mut contract = Contract::new_default_meta(
+        account("doomslug.testnet")
+    );
+    //the default token type is free, premium tokens cost 2 NEAR
+    contract.set_mint_price(
+        Some("premium".to_string()),
+        Some(MintPrice { amount: U128(2 * ONE_NEAR), ft_contract_id: None })
+    );
+    assert!(contract.mint_price(None).is_none());
+
+    //the price is collected on top of the storage deposit
+    context.attached_deposit = 3 * ONE_NEAR;
+    testing_env!(context.clone());
+    contract.nft_mint(
+        "001".to_string(),
+        meta("nft#1"),
+        account("spensa.testnet"),
+        None,
+        Some("premium".to_string())
+    );
+    let revenue = contract.treasury_revenue();
+    assert_eq!(revenue.len(), 1);
+    assert_eq!(revenue[0].ft_contract_id, None);
+    assert_eq!(revenue[0].collected, U128(2 * ONE_NEAR));
+    assert_eq!(revenue[0].balance, U128(2 * ONE_NEAR));
+
+    //the owner withdraws part of the revenue, which is credited back if the transfer fails
+    context.attached_deposit = 1;
+    testing_env!(context.clone());
+    contract.treasury_withdraw(account("treasury.testnet"), U128(ONE_NEAR), None);
+    assert_eq!(contract.treasury_revenue()[0].balance, U128(ONE_NEAR));
+    context.predecessor_account_id = account("spensa.testnet");
+    testing_env!(
+        context.clone(),
+        VMConfig::test(),
+        RuntimeFeesConfig::test(),
+        Default::default(),
+        vec![PromiseResult::Failed]
+    );
+    assert!(!contract.resolve_treasury_withdraw(None, U128(ONE_NEAR)));
+    assert_eq!(contract.treasury_revenue()[0].balance, U128(2 * ONE_NEAR));
+    assert_eq!(contract.treasury_revenue()[0].withdrawn, U128(0));
+}
+
+
+#[test]
+pub fn test_mint_price_removal() {
+    //set up the testing context
+    let context = get_context(
+        account("doomslug.testnet")
+    );
+    testing_env!(context);
+    let mut contract = Contract::new_default_meta(
+        account("doomslug.testnet")
+    );
+    let near_price = |amount| Some(MintPrice { amount: U128(amount), ft_contract_id: None });
+    contract.set_mint_price(None, near_price(ONE_NEAR));
+    contract.set_mint_price(Some("premium".to_string()), near_price(2 * ONE_NEAR));
+
+    //removing the price of a type makes the default price apply to it again
+    contract.set_mint_price(Some("premium".to_string()), None);
+    assert_eq!(contract.mint_price(Some("premium".to_string())).unwrap().amount, U128(ONE_NEAR));
+
+    //a zero price makes the type free whatever the default price
+    contract.set_mint_price(Some("premium".to_string()), near_price(0));
+    assert_eq!(contract.mint_price(Some("premium".to_string())).unwrap().amount, U128(0));
+
+    //removing the default price makes the types without a price free
+    contract.set_mint_price(None, None);
+    assert!(contract.mint_price(Some("basic".to_string())).is_none());
+}
+
+#[test]
+#[should_panic(expected = "to cover storage")]
+pub fn test_paid_mint_insufficient_deposit() {
+    //set up the testing context
+    let mut context = get_context(
+        account("doomslug.testnet")
+    );
+    testing_env!(context.clone());
+    let mut contract = Contract::new_default_meta(
+        account("doomslug.testnet")
+    );
+    contract.set_mint_price(None, Some(MintPrice { amount: U128(ONE_NEAR), ft_contract_id: None }));
+
+    //the deposit covers the storage but not the mint price
+    context.attached_deposit = u128::pow(10, 23);
+    testing_env!(context.clone());
+    contract.nft_mint(
+        "001".to_string(),
+        meta("nft#1"),
+        account("spensa.testnet"),
+        None,
+        None
+    );
+}
+
+
+#[test]
+pub fn test_paid_mint_with_ft() {
+    //set up the testing context
+    let mut context = get_context(
+        account("doomslug.testnet")
+    );
+    testing_env!(context.clone());
+    let mut contract = Contract::new_default_meta(
+        account("doomslug.testnet")
+    );
+    contract.set_mint_policy(no_cooldown_policy());
+    let usdc = account("usdc.testnet");
+    contract.add_accepted_ft(usdc.clone());
+    contract.set_mint_price(None, Some(MintPrice { amount: U128(50), ft_contract_id: Some(usdc.clone()) }));
+
+    //the FT contract credits the tokens transferred in by the minter
+    context.predecessor_account_id = usdc.clone();
+    testing_env!(context.clone());
+    contract.ft_on_transfer(account("doomslug.testnet"), U128(80), "{}".to_string());
+    assert_eq!(contract.ft_credit(account("doomslug.testnet"), usdc.clone()), U128(80));
+
+    //the first mint is paid out of the credit, the second one can't be afforded
+    context.predecessor_account_id = account("doomslug.testnet");
+    context.attached_deposit = u128::pow(10, 24);
+    testing_env!(context.clone());
+    let outcomes = contract.nft_batch_mint(
+        vec![
+            mint_request("001", "nft#1", "spensa.testnet"),
+            mint_request("002", "nft#2", "spensa.testnet"),
+        ],
+        Some(BatchMintMode::BestEffort)
+    );
+    assert!(outcomes[0].successful_operation);
+    assert!(!outcomes[1].successful_operation);
+    assert!(outcomes[1].error.as_ref().unwrap().starts_with("Payment error"));
+    assert_eq!(contract.ft_credit(account("doomslug.testnet"), usdc.clone()), U128(30));
+    let revenue = contract.treasury_revenue();
+    assert_eq!(revenue[0].ft_contract_id, Some(usdc));
+    assert_eq!(revenue[0].collected, U128(50));
+}
+
+
+//the value returned by ft_on_transfer(): the amount of tokens to refund
+fn refunded(result: PromiseOrValue<U128>) -> u128 {
+    match result {
+        PromiseOrValue::Value(amount) => amount.0,
+        PromiseOrValue::Promise(_) => panic!("ft_on_transfer() should return a value"),
+    }
+}
+
+#[test]
+pub fn test_ft_credit() {
+    //set up the testing context
+    let mut context = get_context(
+        account("doomslug.testnet")
+    );
+    testing_env!(context.clone());
+    let mut contract = Contract::new_default_meta(
+        account("doomslug.testnet")
+    );
+    let usdc = account("usdc.testnet");
+    contract.add_accepted_ft(usdc.clone());
+    contract.set_mint_price(
+        Some("premium".to_string()),
+        Some(MintPrice { amount: U128(50), ft_contract_id: Some(usdc.clone()) })
+    );
+
+    //a transfer without a mint intent, or not covering the price, doesn't open a credit
+    context.predecessor_account_id = usdc.clone();
+    testing_env!(context.clone());
+    let rainbow = account("rainbow.testnet");
+    assert_eq!(80, refunded(contract.ft_on_transfer(rainbow.clone(), U128(80), "".to_string())));
+    assert_eq!(80, refunded(contract.ft_on_transfer(rainbow.clone(), U128(80), "{}".to_string())));
+    let premium = r#"{"token_type": "premium"}"#.to_string();
+    assert_eq!(40, refunded(contract.ft_on_transfer(rainbow.clone(), U128(40), premium.clone())));
+    assert_eq!(contract.ft_credit(rainbow.clone(), usdc.clone()), U128(0));
+
+    //a mint intent covering the price opens the credit, which can then be topped up
+    assert_eq!(0, refunded(contract.ft_on_transfer(rainbow.clone(), U128(50), premium)));
+    assert_eq!(0, refunded(contract.ft_on_transfer(rainbow.clone(), U128(5), "".to_string())));
+    assert_eq!(contract.ft_credit(rainbow.clone(), usdc.clone()), U128(55));
+
+    //the account withdraws part of its credit, which is credited back if the transfer fails
+    context.predecessor_account_id = rainbow.clone();
+    context.attached_deposit = 1;
+    testing_env!(context.clone());
+    contract.ft_withdraw_credit(usdc.clone(), Some(U128(20)));
+    assert_eq!(contract.ft_credit(rainbow.clone(), usdc.clone()), U128(35));
+    context.predecessor_account_id = account("spensa.testnet");
+    testing_env!(
+        context.clone(),
+        VMConfig::test(),
+        RuntimeFeesConfig::test(),
+        Default::default(),
+        vec![PromiseResult::Failed]
+    );
+    assert!(!contract.resolve_ft_credit_withdraw(usdc.clone(), rainbow.clone(), U128(20)));
+    assert_eq!(contract.ft_credit(rainbow.clone(), usdc.clone()), U128(55));
+
+    //withdrawing the whole credit removes it
+    context.predecessor_account_id = rainbow.clone();
+    testing_env!(context);
+    contract.ft_withdraw_credit(usdc.clone(), None);
+    assert!(contract.ft_credits.get(&(usdc, rainbow)).is_none());
+}
+
+#[test]
+#[should_panic(expected = "is not an accepted fungible token")]
+pub fn test_ft_on_transfer_not_accepted() {
+    //set up the testing context
+    let mut context = get_context(
+        account("doomslug.testnet")
+    );
+    testing_env!(context.clone());
+    let mut contract = Contract::new_default_meta(
+        account("doomslug.testnet")
+    );
+
+    //tokens the contract doesn't accept are refunded by the FT contract
+    context.predecessor_account_id = account("usdc.testnet");
+    testing_env!(context.clone());
+    contract.ft_on_transfer(account("doomslug.testnet"), U128(80), "".to_string());
+}
+
+
+#[test]
+#[should_panic(expected = "Whitelist error: your grant only allows minting to spensa.testnet")]
+pub fn test_mint_grant_wrong_receiver() {
+    //set up the testing context
+    let mut context = get_context(
+        account("doomslug.testnet")
+    );
+    testing_env!(context.clone());
+    let mut contract = Contract::new_default_meta(
+        account("doomslug.testnet")
+    );
+    context.attached_deposit = u128::pow(10, 23);
+    testing_env!(context.clone());
+    contract.add_to_whitelist(&account("benji.testnet"), grant("spensa.testnet"));
+
+    //the grantee can't mint to another receiver than the one in their grant
+    context.attached_deposit = u128::pow(10, 23);
+    context.signer_account_id = account("benji.testnet");
+    testing_env!(context.clone());
+    contract.nft_mint(
+        "001".to_string(),
+        meta("nft#1"),
+        account("rainbow.testnet"),
+        None,
+        None
+    );
+}
+
+
+#[test]
+pub fn test_mint_grant_media_and_uses() {
+    //set up the testing context
+    let mut context = get_context(
+        account("doomslug.testnet")
+    );
+    testing_env!(context.clone());
+    let mut contract = Contract::new_default_meta(
+        account("doomslug.testnet")
+    );
+    contract.set_mint_policy(no_cooldown_policy());
+    context.attached_deposit = u128::pow(10, 23);
+    testing_env!(context.clone());
+    contract.add_to_whitelist(
+        &account("benji.testnet"),
+        WhitelistGrant {
+            media_hash: Some(Base64VecU8(env::sha256("nft#1".as_bytes()))),
+            uses: 2,
+            ..grant("spensa.testnet")
+        }
+    );
+
+    //only the media in the grant can be minted, and each token uses the grant once
+    context.attached_deposit = u128::pow(10, 24);
+    context.signer_account_id = account("benji.testnet");
+    testing_env!(context.clone());
+    let outcomes = contract.nft_batch_mint(
+        vec![
+            mint_request("001", "nft#2", "spensa.testnet"),
+            mint_request("002", "nft#1", "spensa.testnet"),
+        ],
+        Some(BatchMintMode::BestEffort)
+    );
+    assert_eq!(
+        outcomes[0].error,
+        Some("Whitelist error: your grant doesn't allow minting this media".to_string())
+    );
+    assert!(outcomes[1].successful_operation);
+    assert_eq!(contract.whitelist_grant(account("benji.testnet")).unwrap().uses, 1);
+}
+
+
+#[test]
+#[should_panic(expected = "Whitelist error: your grant has expired")]
+pub fn test_mint_grant_expired() {
+    //set up the testing context
+    let mut context = get_context(
+        account("doomslug.testnet")
+    );
+    testing_env!(context.clone());
+    let mut contract = Contract::new_default_meta(
+        account("doomslug.testnet")
+    );
+    context.attached_deposit = u128::pow(10, 23);
+    testing_env!(context.clone());
+    contract.add_to_whitelist(
+        &account("spensa.testnet"),
+        WhitelistGrant { expires_at: Some(U64(NANOS_PER_SEC)), ..grant("spensa.testnet") }
+    );
+
+    //the grant can't be used after its expiry
+    context.attached_deposit = u128::pow(10, 23);
+    context.signer_account_id = account("spensa.testnet");
+    context.block_timestamp = 2 * NANOS_PER_SEC;
+    testing_env!(context.clone());
+    contract.nft_mint(
+        "001".to_string(),
+        meta("nft#1"),
+        account("spensa.testnet"),
+        None,
+        None
+    );
+}
+
+
+#[test]
+pub fn test_whitelist_tickets() {
+    //set up the testing context
+    let mut context = get_context(
+        account("doomslug.testnet")
+    );
+    testing_env!(context.clone());
+    let mut contract = Contract::new_default_meta(
+        account("doomslug.testnet")
+    );
+    contract.set_mint_policy(no_cooldown_policy());
+
+    //whitelist an account for two mints, and another one for a single day
+    context.attached_deposit = u128::pow(10, 23);
+    testing_env!(context.clone());
+    contract.add_to_whitelist(
+        &account("spensa.testnet"),
+        WhitelistGrant { uses: 2, reason: "Score refresh".to_string(), ..grant("spensa.testnet") }
+    );
+    contract.add_to_whitelist(
+        &account("benji.testnet"),
+        WhitelistGrant { expires_at: Some(U64(NANOS_PER_DAY)), ..grant("benji.testnet") }
+    );
+    let entries = contract.whitelist(None, None);
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].account_id, account("spensa.testnet"));
+    assert_eq!(entries[0].grant.reason, "Score refresh".to_string());
+    assert_eq!(contract.whitelist(Some(U128(1)), Some(1))[0].account_id, account("benji.testnet"));
+
+    //the multi-use grant survives its first use
+    context.signer_account_id = account("spensa.testnet");
+    testing_env!(context.clone());
+    contract.nft_mint(
+        "001".to_string(),
+        meta("nft#1"),
+        account("spensa.testnet"),
+        None,
+        None
+    );
+    assert!(contract.is_whitelisted(account("spensa.testnet")));
+    assert_eq!(contract.whitelist_grant(account("spensa.testnet")).unwrap().uses, 1);
+
+    //once expired, a grant is left out of the views, then pruned by anyone
+    context.signer_account_id = account("rainbow.testnet");
+    context.predecessor_account_id = account("rainbow.testnet");
+    context.attached_deposit = 0;
+    context.block_timestamp = 2 * NANOS_PER_DAY;
+    context.storage_usage = env::storage_usage();
+    testing_env!(context.clone());
+    assert!(!contract.is_whitelisted(account("benji.testnet")));
+    assert_eq!(contract.whitelist(None, None).len(), 1);
+    assert_eq!(contract.prune_whitelist(None), 1);
+    assert!(contract.whitelist_grant(account("benji.testnet")).is_none());
+    assert!(contract.whitelist_grant(account("spensa.testnet")).is_some());
+}
+
+
+//set up a contract where the owner minted token "001" to themselves, then approved market.testnet
+//to transfer it until the given timestamp
+fn approved_contract(context: &mut VMContext, expires_at: Option<u64>) -> Contract {
+    testing_env!(context.clone());
+    let mut contract = Contract::new_default_meta(
+        account("doomslug.testnet")
+    );
+    context.attached_deposit = u128::pow(10, 23);
+    testing_env!(context.clone());
+    contract.nft_mint(
+        "001".to_string(),
+        meta("nft#1"),
+        account("doomslug.testnet"),
+        None,
+        None
+    );
+    context.storage_usage = env::storage_usage();
+    testing_env!(context.clone());
+    contract.nft_approve("001".to_string(), account("market.testnet"), None, expires_at.map(U64));
+    contract
+}
+
+
+#[test]
+fn test_approval_expiry() {
+    //set up the testing context
+    let mut context = get_context(
+        account("doomslug.testnet")
+    );
+    context.block_timestamp = NANOS_PER_DAY;
+    let mut contract = approved_contract(&mut context, Some(2 * NANOS_PER_DAY));
+
+    //the approval is listed with its expiry, and logged
+    let approvals = contract.nft_approvals("001".to_string());
+    assert_eq!(approvals.len(), 1);
+    assert_eq!(approvals[0].account_id, account("market.testnet"));
+    assert_eq!(approvals[0].approval_id, 0);
+    assert_eq!(approvals[0].expires_at, Some(U64(2 * NANOS_PER_DAY)));
+    let logs = get_logs();
+    assert!(logs.last().unwrap().contains(r#""event":"nft_approve""#));
+    assert!(contract.nft_is_approved("001".to_string(), account("market.testnet"), None));
+
+    //re-approving without an expiry drops the previous one
+    contract.nft_approve("001".to_string(), account("market.testnet"), None, None);
+    let approvals = contract.nft_approvals("001".to_string());
+    assert_eq!(approvals[0].approval_id, 1);
+    assert_eq!(approvals[0].expires_at, None);
+
+    //revoking logs the revoked accounts
+    context.storage_usage = env::storage_usage();
+    context.attached_deposit = 1;
+    testing_env!(context.clone());
+    contract.nft_revoke_all("001".to_string());
+    assert!(contract.nft_approvals("001".to_string()).is_empty());
+    assert_eq!(
+        get_logs(),
+        vec![r#"EVENT_JSON:{"standard":"nearoracle","version":"1.0.0","event":"nft_revoke","data":[{"owner_id":"doomslug.testnet","token_id":"001","account_ids":["market.testnet"]}]}"#]
+    );
+}
+
+
+#[test]
+#[should_panic(expected = "Approval expired")]
+fn test_transfer_with_expired_approval() {
+    //set up the testing context
+    let mut context = get_context(
+        account("doomslug.testnet")
+    );
+    context.block_timestamp = NANOS_PER_DAY;
+    let mut contract = approved_contract(&mut context, Some(2 * NANOS_PER_DAY));
+
+    //once the approval expired, the approved account can't transfer the token anymore
+    context.block_timestamp = 2 * NANOS_PER_DAY;
+    context.predecessor_account_id = account("market.testnet");
+    context.attached_deposit = 1;
+    testing_env!(context.clone());
+    assert!(!contract.nft_is_approved("001".to_string(), account("market.testnet"), None));
+    contract.nft_transfer(account("spensa.testnet"), "001".to_string(), None, None);
+}
+
+
+#[test]
+fn test_transfer_payout_refunds_approvals() {
+    //set up the testing context
+    let mut context = get_context(
+        account("doomslug.testnet")
+    );
+    context.block_timestamp = NANOS_PER_DAY;
+    let mut contract = approved_contract(&mut context, Some(2 * NANOS_PER_DAY));
+    assert!(contract.approval_expiries.get(&("001".to_string(), 0)).is_some());
+
+    //the approved marketplace sells the token: the approval and its expiry are cleared
+    context.storage_usage = env::storage_usage();
+    context.predecessor_account_id = account("market.testnet");
+    context.attached_deposit = 1;
+    testing_env!(context.clone());
+    let payout = contract.nft_transfer_payout(account("spensa.testnet"), "001".to_string(), 0, None, U128(1_000), 10);
+    assert_eq!(payout.payout.get(&account("doomslug.testnet")), Some(&U128(1_000)));
+    assert_eq!(contract.whose_token("001".to_string()), Some(account("spensa.testnet")));
+    assert!(contract.approval_expiries.get(&("001".to_string(), 0)).is_none());
+}
+
+
+#[test]
+fn test_operator_transfer() {
+    //set up the testing context
+    let mut context = get_context(
+        account("doomslug.testnet")
+    );
+    let mut contract = approved_contract(&mut context, None);
+
+    //the owner approves an operator for all of their tokens
+    context.storage_usage = env::storage_usage();
+    testing_env!(context.clone());
+    assert!(contract.nft_approve_operator(account("wallet.testnet")));
+    assert!(!contract.nft_approve_operator(account("wallet.testnet")));
+    assert!(contract.nft_is_operator(account("doomslug.testnet"), account("wallet.testnet")));
+    assert_eq!(contract.nft_operators(account("doomslug.testnet"), None, None), vec![account("wallet.testnet")]);
+    assert!(contract.nft_is_approved("001".to_string(), account("wallet.testnet"), None));
+    assert!(!contract.nft_is_approved("001".to_string(), account("wallet.testnet"), Some(0)));
+
+    //the operator transfers the token without a per-token approval
+    context.storage_usage = env::storage_usage();
+    context.predecessor_account_id = account("wallet.testnet");
+    context.attached_deposit = 1;
+    testing_env!(context.clone());
+    contract.nft_transfer(account("spensa.testnet"), "001".to_string(), None, None);
+    assert_eq!(contract.whose_token("001".to_string()), Some(account("spensa.testnet")));
+    assert!(get_logs().last().unwrap().contains(r#""authorized_id":"wallet.testnet""#));
+
+    //the operator only manages the tokens of the owner who approved it
+    assert!(!contract.nft_is_approved("001".to_string(), account("wallet.testnet"), None));
+
+    //revoking logs the operator and removes it
+    context.storage_usage = env::storage_usage();
+    context.predecessor_account_id = account("doomslug.testnet");
+    testing_env!(context.clone());
+    assert!(contract.nft_revoke_operator(account("wallet.testnet")));
+    assert!(!contract.nft_revoke_operator(account("wallet.testnet")));
+    assert!(contract.nft_operators(account("doomslug.testnet"), None, None).is_empty());
+    assert_eq!(
+        get_logs(),
+        vec![r#"EVENT_JSON:{"standard":"nearoracle","version":"1.0.0","event":"nft_operator_revoke","data":[{"owner_id":"doomslug.testnet","operator_id":"wallet.testnet"}]}"#]
+    );
+}
+
+
+#[test]
+fn test_operator_transfer_with_expired_approval() {
+    //set up the testing context
+    let mut context = get_context(
+        account("doomslug.testnet")
+    );
+    context.block_timestamp = NANOS_PER_DAY;
+    let mut contract = approved_contract(&mut context, Some(2 * NANOS_PER_DAY));
+
+    //the marketplace also becomes an operator of the owner
+    context.storage_usage = env::storage_usage();
+    testing_env!(context.clone());
+    contract.nft_approve_operator(account("market.testnet"));
+
+    //its expired approval doesn't stop it from transferring the token as an operator
+    context.block_timestamp = 2 * NANOS_PER_DAY;
+    context.storage_usage = env::storage_usage();
+    context.predecessor_account_id = account("market.testnet");
+    context.attached_deposit = 1;
+    testing_env!(context.clone());
+    assert!(contract.nft_is_approved("001".to_string(), account("market.testnet"), None));
+    contract.nft_transfer(account("spensa.testnet"), "001".to_string(), None, None);
+    assert_eq!(contract.whose_token("001".to_string()), Some(account("spensa.testnet")));
+}
+
+
+#[test]
+fn test_operator_transfer_payout() {
+    //set up the testing context
+    let mut context = get_context(
+        account("doomslug.testnet")
+    );
+    let mut contract = approved_contract(&mut context, None);
+    context.storage_usage = env::storage_usage();
+    testing_env!(context.clone());
+    contract.nft_approve_operator(account("wallet.testnet"));
+
+    //the operator sells the token through a payout without a per-token approval
+    context.storage_usage = env::storage_usage();
+    context.predecessor_account_id = account("wallet.testnet");
+    context.attached_deposit = 1;
+    testing_env!(context.clone());
+    let payout = contract.nft_transfer_payout(account("spensa.testnet"), "001".to_string(), 0, None, U128(1_000), 10);
+    assert_eq!(payout.payout.get(&account("doomslug.testnet")), Some(&U128(1_000)));
+    assert_eq!(contract.whose_token("001".to_string()), Some(account("spensa.testnet")));
+    assert!(get_logs().iter().any(|log| log.contains(r#""authorized_id":"wallet.testnet""#)));
+}
+
+
+#[test]
+#[should_panic(expected = "Unauthorized")]
+fn test_revoked_operator_transfer() {
+    //set up the testing context
+    let mut context = get_context(
+        account("doomslug.testnet")
+    );
+    let mut contract = approved_contract(&mut context, None);
+    context.storage_usage = env::storage_usage();
+    testing_env!(context.clone());
+    contract.nft_approve_operator(account("wallet.testnet"));
+
+    context.storage_usage = env::storage_usage();
+    context.attached_deposit = 1;
+    testing_env!(context.clone());
+    contract.nft_revoke_operator(account("wallet.testnet"));
+
+    //once revoked, the operator can't transfer the owner's tokens anymore
+    context.predecessor_account_id = account("wallet.testnet");
+    testing_env!(context.clone());
+    contract.nft_transfer(account("spensa.testnet"), "001".to_string(), None, None);
+}
+
+
+//transfer token "001", approved to market.testnet, to receiver.testnet with nft_transfer_call(),
+//then resolve the transfer with the given outcome of nft_on_transfer()
+//returns the contract and the value returned by nft_resolve_transfer()
+fn resolve_transfer(result: PromiseResult, transferred_on: bool) -> (Contract, bool) {
+    //set up the testing context
+    let mut context = get_context(
+        account("doomslug.testnet")
+    );
+    let mut contract = approved_contract(&mut context, None);
+    let approvals = contract.token_by_id.get(&"001".to_string()).unwrap().approved_account_ids;
+
+    context.storage_usage = env::storage_usage();
+    context.attached_deposit = 1;
+    testing_env!(context.clone());
+    contract.nft_transfer_call(account("receiver.testnet"), "001".to_string(), None, None, "msg".to_string());
+
+    //the receiver may have transferred the token on before the resolution
+    if transferred_on {
+        context.storage_usage = env::storage_usage();
+        context.predecessor_account_id = account("receiver.testnet");
+        testing_env!(context.clone());
+        contract.nft_transfer(account("spensa.testnet"), "001".to_string(), None, None);
+    }
+
+    //the callback is made by the contract itself with the outcome of nft_on_transfer()
+    context.storage_usage = env::storage_usage();
+    context.predecessor_account_id = account("spensa.testnet");
+    context.attached_deposit = 0;
+    testing_env!(
+        context.clone(),
+        VMConfig::test(),
+        RuntimeFeesConfig::test(),
+        Default::default(),
+        vec![result]
+    );
+    let transferred = contract.nft_resolve_transfer(
+        None,
+        account("doomslug.testnet"),
+        account("receiver.testnet"),
+        "001".to_string(),
+        approvals,
+        None,
+    );
+    (contract, transferred)
+}
+
+
+#[test]
+fn test_resolve_transfer_kept() {
+    //the receiver returned false: the token stays with them
+    let (contract, transferred) = resolve_transfer(PromiseResult::Successful(b"false".to_vec()), false);
+    assert!(transferred);
+    assert_eq!(contract.whose_token("001".to_string()), Some(account("receiver.testnet")));
+    assert!(contract.nft_approvals("001".to_string()).is_empty());
+}
+
+
+#[test]
+fn test_resolve_transfer_returned() {
+    //the receiver returned true: the token goes back to its owner along with its approvals
+    let (contract, transferred) = resolve_transfer(PromiseResult::Successful(b"true".to_vec()), false);
+    assert!(!transferred);
+    assert_eq!(contract.whose_token("001".to_string()), Some(account("doomslug.testnet")));
+    assert_eq!(contract.nft_approvals("001".to_string())[0].account_id, account("market.testnet"));
+    assert!(get_logs()[1].contains(r#""old_owner_id":"receiver.testnet","new_owner_id":"doomslug.testnet""#));
+}
+
+
+#[test]
+fn test_resolve_transfer_failed() {
+    //nft_on_transfer() panicked or ran out of gas: the token goes back to its owner
+    let (contract, transferred) = resolve_transfer(PromiseResult::Failed, false);
+    assert!(!transferred);
+    assert_eq!(contract.whose_token("001".to_string()), Some(account("doomslug.testnet")));
+}
+
+
+#[test]
+fn test_resolve_transfer_invalid_result() {
+    //nft_on_transfer() returned something else than a boolean: the token goes back to its owner
+    let (contract, transferred) = resolve_transfer(PromiseResult::Successful(b"\"yes\"".to_vec()), false);
+    assert!(!transferred);
+    assert_eq!(contract.whose_token("001".to_string()), Some(account("doomslug.testnet")));
+}
+
+
+#[test]
+fn test_resolve_transfer_already_transferred_on() {
+    //the receiver asked to return the token, but it no longer owns it
+    let (contract, transferred) = resolve_transfer(PromiseResult::Successful(b"true".to_vec()), true);
+    assert!(transferred);
+    assert_eq!(contract.whose_token("001".to_string()), Some(account("spensa.testnet")));
+}
+
+
+#[test]
+fn test_verify_score() {
+    //set up the testing context
+    let mut context = get_context(
+        account("doomslug.testnet")
+    );
+    context.block_timestamp = 10 * NANOS_PER_DAY;
+    testing_env!(context.clone());
+    let mut contract = Contract::new_default_meta(
+        account("doomslug.testnet")
+    );
+    contract.set_mint_policy(no_cooldown_policy());
+
+    //a score of 640 is minted on day 10, a score of 720 on day 20, and a token without score on day 30
+    context.attached_deposit = u128::pow(10, 23);
+    for (day, token_id, extra) in [
+        (10, "001", Some(r#"{"score": 640}"#)),
+        (20, "002", Some(r#"{"score": 720}"#)),
+        (30, "003", None),
+    ] {
+        context.block_timestamp = day * NANOS_PER_DAY;
+        testing_env!(context.clone());
+        let mut metadata = meta(&format!("nft#{}", token_id));
+        metadata.extra = extra.map(|extra| extra.to_string());
+        contract.nft_mint(token_id.to_string(), metadata, account("spensa.testnet"), None, None);
+    }
+    assert_eq!(
+        contract.nft_token_score("001".to_string()),
+        Some(TokenScore { score: 640, issued_at: U64(10 * NANOS_PER_DAY) })
+    );
+    assert_eq!(contract.nft_token_score("003".to_string()), None);
+
+    //the most recent qualifying score is returned
+    let verification = |contract: &Contract, min_score: u16, max_age_days: Option<u64>| {
+        contract.nft_verify_score(account("spensa.testnet"), min_score, max_age_days.map(|days| U64(days * NANOS_PER_DAY)))
+    };
+    assert_eq!(
+        verification(&contract, 600, None),
+        Some(ScoreVerification { token_id: "002".to_string(), score: 720, issued_at: U64(20 * NANOS_PER_DAY) })
+    );
+    assert_eq!(verification(&contract, 700, Some(10)).unwrap().token_id, "002");
+    //no score is high enough, or recent enough
+    assert_eq!(verification(&contract, 750, None), None);
+    assert_eq!(verification(&contract, 600, Some(5)), None);
+    assert_eq!(contract.nft_verify_score(account("rainbow.testnet"), 0, None), None);
+}
+
+
+//the score history of spensa.testnet as returned by the storescore contract
+fn score_history(score: u16, timestamp: u64) -> PromiseResult {
+    PromiseResult::Successful(
+        format!(
+            r#"{{"scores":[{{"score":500,"timestamp":1,"description":"old"}},{{"score":{},"timestamp":{},"description":"latest"}}]}}"#,
+            score, timestamp
+        )
+        .into_bytes(),
+    )
+}
+
+//ask to mint token "001" with the given score to spensa.testnet on a contract linked to a score contract,
+//then resolve the score check with the given score history
+fn score_checked_mint(extra: &str, history: PromiseResult) -> (Contract, MintOutcome) {
+    //set up the testing context
+    let mut context = get_context(
+        account("doomslug.testnet")
+    );
+    testing_env!(context.clone());
+    let mut contract = Contract::new_default_meta(
+        account("doomslug.testnet")
+    );
+    contract.set_score_contract(Some(account("storescore.testnet")));
+
+    let mut metadata = meta("nft#1");
+    metadata.extra = Some(extra.to_string());
+    let request = MintRequest {
+        token_id: "001".to_string(),
+        metadata: metadata.clone(),
+        receiver_id: account("spensa.testnet"),
+        perpetual_royalties: None,
+        token_type: None,
+    };
+    context.attached_deposit = u128::pow(10, 23);
+    testing_env!(context.clone());
+    assert!(matches!(
+        contract.nft_mint("001".to_string(), metadata, account("spensa.testnet"), None, None),
+        PromiseOrValue::Promise(_)
+    ));
+    //nothing is minted until the score is checked
+    assert_eq!(contract.nft_total_supply(), U128(0));
+
+    //the callback is made by the contract itself with the score history
+    context.predecessor_account_id = account("spensa.testnet");
+    context.attached_deposit = 0;
+    testing_env!(
+        context.clone(),
+        VMConfig::test(),
+        RuntimeFeesConfig::test(),
+        Default::default(),
+        vec![history]
+    );
+    let outcome = contract.nft_resolve_score_check(request, account("doomslug.testnet"), U128(u128::pow(10, 23)));
+    (contract, outcome)
+}
+
+
+#[test]
+fn test_score_checked_mint() {
+    //the metadata matches the latest recorded score
+    let (contract, outcome) = score_checked_mint(r#"{"score": 712, "timestamp": "1650000000000000000"}"#, score_history(712, 1_650_000_000_000_000_000));
+    assert!(outcome.successful_operation);
+    assert_eq!(contract.whose_token("001".to_string()), Some(account("spensa.testnet")));
+    assert_eq!(contract.nft_token_score("001".to_string()).unwrap().score, 712);
+}
+
+
+#[test]
+fn test_score_checked_mint_mismatch() {
+    //the metadata claims an older score: the mint is refused and logged
+    let (contract, outcome) = score_checked_mint(r#"{"score": 500, "timestamp": 1}"#, score_history(712, 1_650_000_000_000_000_000));
+    assert!(!outcome.successful_operation);
+    assert!(outcome.error.unwrap().starts_with("Score check error: the metadata doesn't match the latest score"));
+    assert_eq!(contract.nft_total_supply(), U128(0));
+    assert!(get_logs()[0].contains(r#""event":"nft_mint_failure""#));
+
+    //the receiver has no score recorded (query_score_history() panicked)
+    let (contract, outcome) = score_checked_mint(r#"{"score": 712, "timestamp": 1}"#, PromiseResult::Failed);
+    assert_eq!(outcome.error.unwrap(), "Score check error: spensa.testnet has no score recorded");
+    assert_eq!(contract.nft_total_supply(), U128(0));
+}
+
+
+#[test]
+#[should_panic(expected = "Score check error: metadata.extra must hold the score and its timestamp")]
+fn test_score_checked_mint_without_timestamp() {
+    //set up the testing context
+    let context = get_context(
+        account("doomslug.testnet")
+    );
+    testing_env!(context);
+    let mut contract = Contract::new_default_meta(
+        account("doomslug.testnet")
+    );
+    contract.set_score_contract(Some(account("storescore.testnet")));
+
+    let mut metadata = meta("nft#1");
+    metadata.extra = Some(r#"{"score": 712}"#.to_string());
+    contract.nft_mint("001".to_string(), metadata, account("spensa.testnet"), None, None);
+}
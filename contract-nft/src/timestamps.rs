@@ -0,0 +1,96 @@
+use crate::*;
+use near_sdk::serde::{de, Deserializer, Serializer};
+
+/*
+Time model of the contract:
+- internally, every timestamp is a Unix epoch in NANOSECONDS, the unit of env::block_timestamp()
+- the issued_at, expires_at, starts_at and updated_at fields of TokenMetadata are serialized
+  to and from JSON as NEP-177 strings holding a Unix epoch in MILLISECONDS
+- NFTContractMetadata.timestamp is kept in nanoseconds
+ */
+
+pub const NANOS_PER_MILLI: u64 = 1_000_000;
+pub const NANOS_PER_SEC: u64 = 1_000_000_000;
+pub const NANOS_PER_DAY: u64 = 86_400 * NANOS_PER_SEC;
+
+//any stored timestamp below this value can't be a nanosecond timestamp
+//(it would fall in the first days of 1970), so it must be a millisecond one
+const NANOS_LOWER_BOUND: u64 = 1_000_000_000_000_000;
+
+//serde helpers for the NEP-177 timestamp fields of TokenMetadata:
+//nanoseconds on chain <-> milliseconds as a string in JSON
+pub(crate) mod millis_string {
+    use super::*;
+
+    pub fn serialize<S>(timestamp: &Option<u64>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match timestamp {
+            Some(nanos) => serializer.serialize_some(&(nanos / NANOS_PER_MILLI).to_string()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let millis = <Option<String> as Deserialize>::deserialize(deserializer)?;
+        millis
+            .map(|millis| {
+                millis
+                    .parse::<u64>()
+                    .map_err(de::Error::custom)?
+                    .checked_mul(NANOS_PER_MILLI)
+                    .ok_or_else(|| de::Error::custom("timestamp out of range"))
+            })
+            .transpose()
+    }
+}
+
+//convert a timestamp that was stored in milliseconds to nanoseconds, leave nanosecond ones untouched
+fn normalize_to_nanos(timestamp: Option<u64>) -> Option<u64> {
+    timestamp.map(|t| if t < NANOS_LOWER_BOUND { t * NANOS_PER_MILLI } else { t })
+}
+
+#[near_bindgen]
+impl Contract {
+    /*
+    Migrate the timestamps of already minted tokens to the nanosecond time model.
+    Tokens minted before the time model was made explicit may hold expires_at, starts_at
+    and updated_at values in milliseconds: these get converted to nanoseconds.
+    Runs over a paginated range of tokens so it fits in the gas limit, and returns
+    the number of tokens that were updated.
+    This method can be called only by the smart contract owner.
+     */
+    pub fn migrate_token_timestamps(&mut self, from_index: Option<U128>, limit: Option<u64>) -> u64 {
+        //ensure the function was called by the smart contract owner, else panic
+        self.assert_called_by_owner();
+
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+        let token_ids: Vec<TokenId> = self
+            .token_metadata_by_id
+            .keys()
+            .skip(start as usize)
+            .take(limit.unwrap_or(50) as usize)
+            .collect();
+
+        let mut migrated = 0;
+        for token_id in token_ids {
+            let mut metadata = self.token_metadata_by_id.get(&token_id).unwrap();
+            let before = (metadata.issued_at, metadata.expires_at, metadata.starts_at, metadata.updated_at);
+
+            metadata.issued_at = normalize_to_nanos(metadata.issued_at);
+            metadata.expires_at = normalize_to_nanos(metadata.expires_at);
+            metadata.starts_at = normalize_to_nanos(metadata.starts_at);
+            metadata.updated_at = normalize_to_nanos(metadata.updated_at);
+
+            if before != (metadata.issued_at, metadata.expires_at, metadata.starts_at, metadata.updated_at) {
+                self.token_metadata_by_id.insert(&token_id, &metadata);
+                migrated += 1;
+            }
+        }
+        migrated
+    }
+}
@@ -0,0 +1,360 @@
+use crate::*;
+use near_sdk::{ext_contract, Gas, PromiseResult};
+
+const GAS_FOR_FT_TRANSFER: Gas = Gas(10_000_000_000_000);
+const GAS_FOR_RESOLVE_WITHDRAW: Gas = Gas(10_000_000_000_000);
+
+//the token type whose price applies to the tokens minted without a type, or with a type that has no price
+pub const DEFAULT_TOKEN_TYPE: &str = "default";
+
+/*
+Paid minting.
+The owner can set a mint price per token type, either in NEAR or in a NEP-141 fungible token
+the contract accepts. The price is collected at mint into the contract treasury, on top of the storage deposit:
+- NEAR prices are taken out of the deposit attached to the mint call
+- FT prices are taken out of the FT credit of the caller, topped up by calling ft_transfer_call
+  on the FT contract with this contract as the receiver
+The contract pays for the storage of the FT credits, so a transfer only opens a credit when its msg
+is a mint intent such as {"token_type": "premium"} and its amount covers the price of that type in
+that token: any other transfer from an account without a credit is refunded. Existing credits can be
+topped up with any amount, and withdrawn at any time with ft_withdraw_credit().
+The treasury keeps track of the revenue collected in each currency apart from the storage deposits,
+so that the owner can only withdraw what was actually earned from mint fees.
+ */
+
+//the price of minting a token, in yoctoNEAR or in the smallest unit of a NEP-141 token
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MintPrice {
+    pub amount: U128,
+    //the NEP-141 token the price is paid in, None means NEAR
+    pub ft_contract_id: Option<AccountId>,
+}
+
+//revenue collected in a single currency
+#[derive(BorshDeserialize, BorshSerialize, Default)]
+pub struct TreasuryBalance {
+    pub collected: Balance,
+    pub withdrawn: Balance,
+}
+
+//revenue of the treasury in a single currency, as returned by the treasury_revenue() view
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TreasuryRevenue {
+    //the NEP-141 token, None means NEAR
+    pub ft_contract_id: Option<AccountId>,
+    //all-time revenue collected from mint fees
+    pub collected: U128,
+    //all-time revenue withdrawn by the owner
+    pub withdrawn: U128,
+    //revenue that can still be withdrawn
+    pub balance: U128,
+}
+
+//what a transfer of fungible tokens pays for, as passed in the msg of ft_transfer_call
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FtMintIntent {
+    //the type of the tokens to mint, None for the default type
+    #[serde(default)]
+    pub token_type: Option<String>,
+}
+
+#[ext_contract(ext_fungible_token)]
+trait FungibleToken {
+    fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>);
+}
+
+impl Contract {
+    //find the price of minting a token of the given type
+    pub(crate) fn internal_mint_price(&self, token_type: &Option<String>) -> Option<MintPrice> {
+        token_type
+            .as_ref()
+            .and_then(|token_type| self.mint_prices.get(token_type))
+            .or_else(|| self.mint_prices.get(&DEFAULT_TOKEN_TYPE.to_string()))
+    }
+
+    //check that the payer can afford a mint price paid in fungible tokens
+    //(NEAR prices are checked against the attached deposit once the whole call is done)
+    pub(crate) fn internal_check_mint_price(&self, payer_id: &AccountId, price: &MintPrice) -> Result<(), String> {
+        if let Some(ft_contract_id) = &price.ft_contract_id {
+            let credit = self.ft_credits.get(&(ft_contract_id.clone(), payer_id.clone())).unwrap_or(0);
+            if credit < price.amount.0 {
+                return Err(format!(
+                    "Payment error: the mint price is {} of {}, but your credit is {}",
+                    price.amount.0, ft_contract_id, credit
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    //collect a mint price into the treasury and return the amount of NEAR that was charged
+    pub(crate) fn internal_collect_mint_price(&mut self, payer_id: &AccountId, price: &Option<MintPrice>) -> Balance {
+        let price = match price {
+            Some(price) => price,
+            None => return 0,
+        };
+        if let Some(ft_contract_id) = &price.ft_contract_id {
+            let key = (ft_contract_id.clone(), payer_id.clone());
+            let credit = self.ft_credits.get(&key).unwrap_or(0);
+            assert!(credit >= price.amount.0, "Payment error: not enough credit of {}", ft_contract_id);
+            self.internal_set_ft_credit(&key, credit - price.amount.0);
+        }
+        let mut balance = self.treasury.get(&price.ft_contract_id).unwrap_or_default();
+        balance.collected += price.amount.0;
+        self.treasury.insert(&price.ft_contract_id, &balance);
+
+        match price.ft_contract_id {
+            Some(_) => 0,
+            None => price.amount.0,
+        }
+    }
+
+    //give back a mint price collected by internal_collect_mint_price(), for a mint that was rolled back
+    pub(crate) fn internal_cancel_mint_price(&mut self, payer_id: &AccountId, price: &Option<MintPrice>) {
+        let price = match price {
+            Some(price) => price,
+            None => return,
+        };
+        if let Some(ft_contract_id) = &price.ft_contract_id {
+            let key = (ft_contract_id.clone(), payer_id.clone());
+            let credit = self.ft_credits.get(&key).unwrap_or(0);
+            self.internal_set_ft_credit(&key, credit + price.amount.0);
+        }
+        let mut balance = self.treasury.get(&price.ft_contract_id).unwrap_or_default();
+        balance.collected -= price.amount.0;
+        //drop the entry if the price created it, so that the storage it took is released
+        if balance.collected == 0 && balance.withdrawn == 0 {
+            self.treasury.remove(&price.ft_contract_id);
+        } else {
+            self.treasury.insert(&price.ft_contract_id, &balance);
+        }
+    }
+
+    //set the FT credit of an account, removing the entry (and releasing its storage) once it's spent
+    pub(crate) fn internal_set_ft_credit(&mut self, key: &(AccountId, AccountId), credit: Balance) {
+        if credit == 0 {
+            self.ft_credits.remove(key);
+        } else {
+            self.ft_credits.insert(key, &credit);
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    //Query the price of minting a token of the given type (the default price applies when the type has none)
+    pub fn mint_price(&self, token_type: Option<String>) -> Option<MintPrice> {
+        self.internal_mint_price(&token_type)
+    }
+
+    //Query every mint price, keyed by token type
+    pub fn mint_prices(&self) -> Vec<(String, MintPrice)> {
+        self.mint_prices.to_vec()
+    }
+
+    //Query the NEP-141 tokens mint prices can be paid in
+    pub fn accepted_fts(&self) -> Vec<AccountId> {
+        self.accepted_fts.to_vec()
+    }
+
+    //Query the credit of fungible tokens an account can spend on mint prices
+    pub fn ft_credit(&self, account_id: AccountId, ft_contract_id: AccountId) -> U128 {
+        U128(self.ft_credits.get(&(ft_contract_id, account_id)).unwrap_or(0))
+    }
+
+    //Query the revenue collected from mint fees, per currency
+    pub fn treasury_revenue(&self) -> Vec<TreasuryRevenue> {
+        self.treasury
+            .iter()
+            .map(|(ft_contract_id, balance)| TreasuryRevenue {
+                ft_contract_id,
+                collected: U128(balance.collected),
+                withdrawn: U128(balance.withdrawn),
+                balance: U128(balance.collected - balance.withdrawn),
+            })
+            .collect()
+    }
+
+    //Sets the price of minting a token of the given type ("default" when None).
+    //Passing a None price removes the price of the type, so that the default price applies to it again
+    //(removing the default price makes the types without a price free); a zero amount makes a type free.
+    //This method can be called only by the smart contract owner.
+    pub fn set_mint_price(&mut self, token_type: Option<String>, price: Option<MintPrice>) {
+        //ensure the function was called by the smart contract owner, else panic
+        self.assert_called_by_owner();
+
+        let token_type = token_type.unwrap_or_else(|| DEFAULT_TOKEN_TYPE.to_string());
+        match price {
+            Some(price) => {
+                if let Some(ft_contract_id) = &price.ft_contract_id {
+                    assert!(
+                        self.accepted_fts.contains(ft_contract_id),
+                        "{} is not an accepted fungible token",
+                        ft_contract_id
+                    );
+                }
+                self.mint_prices.insert(&token_type, &price);
+            }
+            None => {
+                self.mint_prices.remove(&token_type);
+            }
+        }
+    }
+
+    //Adds a NEP-141 token to the tokens mint prices can be paid in.
+    //This method can be called only by the smart contract owner.
+    pub fn add_accepted_ft(&mut self, ft_contract_id: AccountId) -> bool {
+        //ensure the function was called by the smart contract owner, else panic
+        self.assert_called_by_owner();
+
+        self.accepted_fts.insert(&ft_contract_id)
+    }
+
+    //Removes a NEP-141 token from the tokens mint prices can be paid in.
+    //The credits already transferred in can still be spent on the prices set in that token.
+    //This method can be called only by the smart contract owner.
+    pub fn remove_accepted_ft(&mut self, ft_contract_id: AccountId) -> bool {
+        //ensure the function was called by the smart contract owner, else panic
+        self.assert_called_by_owner();
+
+        self.accepted_fts.remove(&ft_contract_id)
+    }
+
+    //NEP-141 receiver: credits the fungible tokens transferred in by sender_id,
+    //so that they can spend them on mint prices.
+    //A sender without a credit must pass a mint intent as msg, e.g. {"token_type": "premium"}
+    //or {} for the default type, and transfer at least the price of that type in that token.
+    //Returns the amount of tokens to refund to the sender: none, or all of them if the transfer is refused.
+    pub fn ft_on_transfer(&mut self, sender_id: AccountId, amount: U128, msg: String) -> PromiseOrValue<U128> {
+        let ft_contract_id = env::predecessor_account_id();
+        assert!(
+            self.accepted_fts.contains(&ft_contract_id),
+            "{} is not an accepted fungible token",
+            ft_contract_id
+        );
+
+        let key = (ft_contract_id, sender_id);
+        let credit = self.ft_credits.get(&key).unwrap_or(0);
+        //opening a credit takes storage the contract pays for: only do it for an actual mint
+        if credit == 0 {
+            let intent: FtMintIntent = match near_sdk::serde_json::from_str(&msg) {
+                Ok(intent) => intent,
+                Err(_) => {
+                    env::log_str("Payment error: the msg must be a mint intent, e.g. {\"token_type\": null}");
+                    return PromiseOrValue::Value(amount);
+                }
+            };
+            let covers_price = self.internal_mint_price(&intent.token_type).map_or(false, |price| {
+                price.ft_contract_id.as_ref() == Some(&key.0) && price.amount.0 <= amount.0
+            });
+            if !covers_price {
+                env::log_str(&format!(
+                    "Payment error: the transfer doesn't cover the price of this token type in {}",
+                    key.0
+                ));
+                return PromiseOrValue::Value(amount);
+            }
+        }
+        self.ft_credits.insert(&key, &(credit + amount.0));
+
+        PromiseOrValue::Value(U128(0))
+    }
+
+    #[payable]
+    //Withdraws the caller's unspent credit of the given NEP-141 token, all of it when amount is None.
+    //The caller must attach exactly 1 yoctoNEAR.
+    pub fn ft_withdraw_credit(&mut self, ft_contract_id: AccountId, amount: Option<U128>) -> Promise {
+        //assert that the user attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+
+        let account_id = env::predecessor_account_id();
+        let key = (ft_contract_id.clone(), account_id.clone());
+        let credit = self.ft_credits.get(&key).unwrap_or(0);
+        let amount = amount.map_or(credit, |amount| amount.0);
+        assert!(amount > 0, "The amount to withdraw must be positive");
+        assert!(amount <= credit, "Your credit of {} is only {}", ft_contract_id, credit);
+        //the withdrawal is accounted for up front, and reverted if the transfer fails
+        self.internal_set_ft_credit(&key, credit - amount);
+
+        ext_fungible_token::ext(ft_contract_id.clone())
+            .with_attached_deposit(1)
+            .with_static_gas(GAS_FOR_FT_TRANSFER)
+            .ft_transfer(account_id.clone(), U128(amount), Some("Credit withdrawal".to_string()))
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_RESOLVE_WITHDRAW)
+                    .resolve_ft_credit_withdraw(ft_contract_id, account_id, U128(amount)),
+            )
+    }
+
+    #[private]
+    //resolves the transfer of a credit withdrawal:
+    //if it failed, the amount is credited back to the account.
+    //Returns whether the withdrawal went through.
+    pub fn resolve_ft_credit_withdraw(&mut self, ft_contract_id: AccountId, account_id: AccountId, amount: U128) -> bool {
+        if let PromiseResult::Successful(_) = env::promise_result(0) {
+            return true;
+        }
+        let key = (ft_contract_id, account_id);
+        let credit = self.ft_credits.get(&key).unwrap_or(0);
+        self.ft_credits.insert(&key, &(credit + amount.0));
+        false
+    }
+
+    #[payable]
+    //Withdraws revenue from the treasury to the beneficiary account, in NEAR or in the given NEP-141 token.
+    //Only the revenue collected from mint fees can be withdrawn, never the storage deposits.
+    //This method can be called only by the smart contract owner.
+    pub fn treasury_withdraw(
+        &mut self,
+        beneficiary_id: AccountId,
+        amount: U128,
+        ft_contract_id: Option<AccountId>,
+    ) -> Promise {
+        //assert that the user attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+        //ensure the function was called by the smart contract owner, else panic
+        self.assert_called_by_owner();
+
+        let mut balance = self.treasury.get(&ft_contract_id).unwrap_or_default();
+        assert!(amount.0 > 0, "The amount to withdraw must be positive");
+        assert!(
+            amount.0 <= balance.collected - balance.withdrawn,
+            "The treasury holds only {} of this currency",
+            balance.collected - balance.withdrawn
+        );
+        //the withdrawal is accounted for up front, and reverted if the transfer fails
+        balance.withdrawn += amount.0;
+        self.treasury.insert(&ft_contract_id, &balance);
+
+        let transfer = match &ft_contract_id {
+            None => Promise::new(beneficiary_id).transfer(amount.0),
+            Some(ft_contract_id) => ext_fungible_token::ext(ft_contract_id.clone())
+                .with_attached_deposit(1)
+                .with_static_gas(GAS_FOR_FT_TRANSFER)
+                .ft_transfer(beneficiary_id, amount, Some("Treasury withdrawal".to_string())),
+        };
+        transfer.then(
+            Self::ext(env::current_account_id())
+                .with_static_gas(GAS_FOR_RESOLVE_WITHDRAW)
+                .resolve_treasury_withdraw(ft_contract_id, amount),
+        )
+    }
+
+    #[private]
+    //resolves the transfer of a treasury withdrawal:
+    //if it failed, the amount is credited back to the treasury.
+    //Returns whether the withdrawal went through.
+    pub fn resolve_treasury_withdraw(&mut self, ft_contract_id: Option<AccountId>, amount: U128) -> bool {
+        if let PromiseResult::Successful(_) = env::promise_result(0) {
+            return true;
+        }
+        let mut balance = self.treasury.get(&ft_contract_id).unwrap_or_default();
+        balance.withdrawn -= amount.0;
+        self.treasury.insert(&ft_contract_id, &balance);
+        false
+    }
+}
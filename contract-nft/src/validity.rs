@@ -0,0 +1,63 @@
+use crate::*;
+use near_sdk::json_types::U64;
+
+/*
+A score NFT can carry a validity window through the 'starts_at' and 'expires_at'
+fields of its TokenMetadata. A token is valid once 'starts_at' has passed and
+until 'expires_at' is reached; a missing bound means the window is open on that side.
+ */
+
+#[near_bindgen]
+impl Contract {
+    //Is the token currently within its validity window? Query it
+    pub fn nft_is_valid(&self, token_id: TokenId) -> bool {
+        let metadata = self.token_metadata_by_id.get(&token_id).expect("No token");
+        token_is_valid(&metadata, env::block_timestamp())
+    }
+
+    //get the number of tokens of a given owner that are currently valid
+    pub fn nft_valid_supply_for_owner(&self, account_id: &AccountId) -> U128 {
+        //if there isn't a set of tokens for the passed in account ID, we'll return 0
+        let tokens_for_owner_set = match self.tokens_per_owner.get(account_id) {
+            Some(tokens_for_owner_set) => tokens_for_owner_set,
+            None => return U128(0),
+        };
+
+        let now = env::block_timestamp();
+        let count = tokens_for_owner_set
+            .iter()
+            .filter(|token_id| {
+                let metadata = self.token_metadata_by_id.get(token_id).unwrap();
+                token_is_valid(&metadata, now)
+            })
+            .count();
+        U128(count as u128)
+    }
+
+    //Query the default lifetime (in nanoseconds) applied to newly minted tokens
+    pub fn default_token_lifetime(&self) -> Option<U64> {
+        self.default_token_lifetime.map(U64)
+    }
+
+    //Sets the default lifetime (in nanoseconds) applied at mint to tokens without an expires_at.
+    //Passing None means newly minted tokens never expire.
+    //This method can be called only by the smart contract owner.
+    pub fn set_default_token_lifetime(&mut self, lifetime: Option<U64>) {
+        //ensure the function was called by the smart contract owner, else panic
+        self.assert_called_by_owner();
+
+        if let Some(lifetime) = lifetime {
+            assert!(lifetime.0 > 0, "The default token lifetime must be greater than zero");
+        }
+        self.default_token_lifetime = lifetime.map(u64::from);
+    }
+
+    //Sets whether expired tokens are left out of the per-user cap enforced by nft_mint().
+    //This method can be called only by the smart contract owner.
+    pub fn set_mint_cap_ignores_expired(&mut self, ignore_expired: bool) {
+        //ensure the function was called by the smart contract owner, else panic
+        self.assert_called_by_owner();
+
+        self.mint_cap_ignores_expired = ignore_expired;
+    }
+}
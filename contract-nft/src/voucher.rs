@@ -0,0 +1,169 @@
+use crate::*;
+use ed25519_dalek::Verifier;
+use near_sdk::json_types::U64;
+use near_sdk::CurveType;
+
+/*
+Lazy minting via mint vouchers.
+Rather than whitelisting every user before they can mint, the oracle backend signs
+an off-chain voucher with an ed25519 key registered on the contract by the owner.
+The receiver then redeems the voucher with nft_mint_with_voucher(), paying for their own storage.
+
+The signed message is the borsh serialization of the tuple (contract_account_id, voucher),
+so a voucher can't be redeemed on another contract. The voucher's 'metadata_hash' is the
+sha256 hash of the borsh serialization of the TokenMetadata passed at redemption.
+Every nonce can be redeemed only once.
+ */
+
+#[derive(BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MintVoucher {
+    //the only account that can redeem the voucher, and the owner of the minted token
+    pub receiver_id: AccountId,
+    //the ID of the token to be minted
+    pub token_id: TokenId,
+    //sha256 hash of the borsh-serialized token metadata
+    pub metadata_hash: Base64VecU8,
+    //when the voucher stops being redeemable, Unix epoch in nanoseconds
+    pub expires_at: U64,
+    //unique number preventing the voucher from being replayed
+    pub nonce: U64,
+    //the type of the token, which sets its mint price
+    #[serde(default)]
+    pub token_type: Option<String>,
+}
+
+//check an ed25519 signature of a message against a NEAR public key
+pub(crate) fn verify_ed25519(public_key: &PublicKey, message: &[u8], signature: &[u8]) -> bool {
+    //the first byte of a NEAR public key is its curve type
+    let public_key = match ed25519_dalek::PublicKey::from_bytes(&public_key.as_bytes()[1..]) {
+        Ok(public_key) => public_key,
+        Err(_) => return false,
+    };
+    match ed25519_dalek::Signature::try_from(signature) {
+        Ok(signature) => public_key.verify(message, &signature).is_ok(),
+        Err(_) => false,
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    //redeem a mint voucher signed by the registered voucher signer and mint the token it describes.
+    //Only the voucher's receiver can redeem it, and they pay for the storage of their token.
+    //Vouchers can't be redeemed while scores are checked against a score contract, whose check they would skip.
+    pub fn nft_mint_with_voucher(
+        &mut self,
+        voucher: MintVoucher,
+        signature: Base64VecU8,
+        metadata: TokenMetadata,
+        perpetual_royalties: Option<HashMap<AccountId, u32>>,
+    ) -> MintOutcome {
+        //measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        //SCORE CHECK
+        //voucher mints aren't checked against the score contract, so they're refused while one is configured
+        if let Some(score_contract_id) = &self.score_contract_id {
+            env::panic_str(&format!(
+                "Voucher minting is unavailable while scores are checked against {}",
+                score_contract_id
+            ));
+        }
+
+        //VOUCHER CHECKS
+        let signer = self
+            .voucher_signer
+            .as_ref()
+            .expect("Voucher error: no voucher signer is registered on the contract");
+        assert_eq!(
+            env::predecessor_account_id(),
+            voucher.receiver_id,
+            "Voucher error: only the receiver of the voucher can redeem it"
+        );
+        assert!(
+            env::block_timestamp() < voucher.expires_at.0,
+            "Voucher error: the voucher has expired"
+        );
+        let message = (env::current_account_id(), &voucher).try_to_vec().unwrap();
+        assert!(
+            verify_ed25519(signer, &message, &signature.0),
+            "Voucher error: invalid signature"
+        );
+        assert_eq!(
+            env::sha256(&metadata.try_to_vec().unwrap()),
+            voucher.metadata_hash.0,
+            "Voucher error: the metadata doesn't match the voucher"
+        );
+        //mark the nonce as used, so that the voucher can't be replayed
+        assert!(
+            self.used_voucher_nonces.insert(&voucher.nonce.0),
+            "Voucher error: the voucher was already redeemed"
+        );
+
+        //LOGIC CHECKS
+        //run every check of the mint policy, and panic with the reason if one fails
+        let prepared = self
+            .internal_prepare_mint(MintRequest {
+                token_id: voucher.token_id,
+                metadata,
+                receiver_id: voucher.receiver_id,
+                perpetual_royalties,
+                token_type: voucher.token_type,
+            }, &env::predecessor_account_id())
+            .unwrap_or_else(|error| env::panic_str(&error));
+
+        //PAYMENT
+        //collect the mint price into the treasury
+        let payment = self.internal_collect_mint_price(&env::predecessor_account_id(), &prepared.price);
+
+        //CORE
+        let (token_id, owner_id) = self.internal_mint(prepared);
+
+        //LOG EVENTS
+        log_nft_mint(vec![NftMintLog {
+            owner_id: owner_id.to_string(),
+            token_ids: vec![token_id.to_string()],
+            memo: None,
+        }]);
+
+        //PAYOUT
+        //the receiver pays for the storage of their token and for the mint price
+        let required_storage_in_bytes = env::storage_usage() - initial_storage_usage;
+        refund_deposit_after_payment(required_storage_in_bytes, payment);
+
+        MintOutcome {
+            gas_used: env::used_gas(),
+            nft_id: token_id,
+            owner_id,
+            successful_operation: true,
+            error: None,
+        }
+    }
+
+    //Query the public key mint vouchers must be signed with
+    pub fn voucher_signer(&self) -> Option<PublicKey> {
+        self.voucher_signer.clone()
+    }
+
+    //Query whether a voucher nonce was already redeemed
+    pub fn is_voucher_redeemed(&self, nonce: U64) -> bool {
+        self.used_voucher_nonces.contains(&nonce.0)
+    }
+
+    //Registers the ed25519 public key mint vouchers must be signed with.
+    //Passing None disables lazy minting.
+    //This method can be called only by the smart contract owner.
+    pub fn set_voucher_signer(&mut self, public_key: Option<PublicKey>) {
+        //ensure the function was called by the smart contract owner, else panic
+        self.assert_called_by_owner();
+
+        if let Some(public_key) = &public_key {
+            assert!(
+                public_key.curve_type() == CurveType::ED25519,
+                "The voucher signer must be an ed25519 public key"
+            );
+        }
+        self.voucher_signer = public_key;
+    }
+}
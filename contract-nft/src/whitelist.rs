@@ -1,11 +1,52 @@
 use near_sdk::{env, AccountId, near_bindgen};
+use near_sdk::json_types::U64;
 use crate::*;
 
+/*
+Whitelist tickets.
+Every whitelisted account holds a grant telling what it can mint, how many times and until when.
+Grants are stored in an UnorderedMap so that they can be listed, and the owner pays for their storage
+when adding them. Grants that are used up are removed at the end of the mint call that used them up,
+while expired grants are removed lazily, either when the owner replaces them or by prune_whitelist(),
+which anyone can call. Either way the storage they released is refunded to the contract owner.
+ */
+
+//a whitelist grant: what a whitelisted account is allowed to mint, and how many times
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct WhitelistGrant {
+    //the only account the grantee can mint tokens to
+    pub receiver_id: AccountId,
+    //sha256 hash of the only media content the grantee can mint, None allows any media
+    pub media_hash: Option<Base64VecU8>,
+    //how many tokens the grantee can still mint
+    pub uses: u32,
+    //when the grant expires, Unix epoch in nanoseconds, None means it never expires
+    pub expires_at: Option<U64>,
+    //why the account was whitelisted
+    #[serde(default)]
+    pub reason: String,
+}
+
+impl WhitelistGrant {
+    //whether the grant can still be used at the given timestamp
+    pub(crate) fn is_active(&self, timestamp: u64) -> bool {
+        self.uses > 0 && self.expires_at.map_or(true, |expires_at| timestamp < expires_at.0)
+    }
+}
+
+//a whitelisted account and its grant, as returned by the whitelist() view
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct WhitelistEntry {
+    pub account_id: AccountId,
+    pub grant: WhitelistGrant,
+}
 
 #[near_bindgen]
 impl Contract {
     //Internal method to verify the predecessor was the smart contract owner
-    fn assert_called_by_owner(&self) {
+    pub(crate) fn assert_called_by_owner(&self) {
         assert_eq!(
             &env::predecessor_account_id(),
             &self.owner_id,
@@ -13,21 +54,162 @@ impl Contract {
         );
     }
 
-    //Adds the given account ID to the whitelist.
-    //Returns `true` if the account id was not in the whitelist before, `false` otherwise.
+    #[payable]
+    //Adds the given account ID to the whitelist with the given grant.
+    //Returns `true` if the account id was not in the whitelist before, `false` otherwise
+    //(in which case its grant is replaced).
+    //The owner must attach enough NEAR to cover the storage of the grant.
     //This method can be called only by the smart contract owner.
-    pub fn add_to_whitelist(&mut self, account_id: &AccountId) -> bool {
+    pub fn add_to_whitelist(&mut self, account_id: &AccountId, grant: WhitelistGrant) -> bool {
 
         //ensure the function was called by the smart contract owner, else panic
         self.assert_called_by_owner();
 
-        self.whitelist.insert(&account_id)
+        assert!(grant.uses > 0, "A whitelist grant must allow at least one use");
+        if let Some(media_hash) = &grant.media_hash {
+            assert_eq!(media_hash.0.len(), 32, "Hash error: media_hash must be a sha256 hash");
+        }
+        if let Some(expires_at) = grant.expires_at {
+            assert!(
+                env::block_timestamp() < expires_at.0,
+                "A whitelist grant can't expire in the past"
+            );
+        }
+
+        //an expired grant doesn't count as being whitelisted
+        let was_whitelisted = self
+            .whitelist
+            .get(account_id)
+            .map_or(false, |grant| grant.is_active(env::block_timestamp()));
+
+        let initial_storage_usage = env::storage_usage();
+        self.whitelist.insert(account_id, &grant);
+        //replacing a grant with a shorter one may release storage
+        let storage_usage = env::storage_usage();
+        if storage_usage >= initial_storage_usage {
+            refund_deposit(storage_usage - initial_storage_usage);
+        } else {
+            refund_released_storage(&self.owner_id, initial_storage_usage - storage_usage);
+        }
+
+        !was_whitelisted
     }
 
-    //Removes the given account ID from the whitelist.
+    //Removes the given account ID from the whitelist and refunds its storage to the owner.
     //Returns `true` if the account id was in the whitelist, `false` otherwise.
     //This method can be called only by the smart contract owner.
     pub fn remove_from_whitelist(&mut self, account_id: &AccountId) -> bool {
-        self.whitelist.remove(&account_id)
+        //ensure the function was called by the smart contract owner, else panic
+        self.assert_called_by_owner();
+
+        self.internal_remove_grant(account_id)
+    }
+
+    //Removes up to 'limit' expired grants from the whitelist and refunds their storage to the owner.
+    //Returns how many grants were removed. Anyone can call this method.
+    pub fn prune_whitelist(&mut self, limit: Option<u64>) -> u64 {
+        let now = env::block_timestamp();
+        let expired: Vec<AccountId> = self
+            .whitelist
+            .iter()
+            .filter(|(_, grant)| !grant.is_active(now))
+            .take(limit.unwrap_or(50) as usize)
+            .map(|(account_id, _)| account_id)
+            .collect();
+
+        for account_id in expired.iter() {
+            self.internal_remove_grant(account_id);
+        }
+        expired.len() as u64
+    }
+
+    //Query the whitelist grant of the given account ID
+    pub fn whitelist_grant(&self, account_id: AccountId) -> Option<WhitelistGrant> {
+        self.whitelist.get(&account_id)
+    }
+
+    //Query whether the given account ID holds a grant that can still be used
+    pub fn is_whitelisted(&self, account_id: AccountId) -> bool {
+        self.whitelist
+            .get(&account_id)
+            .map_or(false, |grant| grant.is_active(env::block_timestamp()))
+    }
+
+    //Query the whitelisted accounts whose grant can still be used, using pagination
+    pub fn whitelist(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<WhitelistEntry> {
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+        let now = env::block_timestamp();
+
+        self.whitelist
+            .iter()
+            //leave out the expired grants, which are waiting to be pruned
+            .filter(|(_, grant)| grant.is_active(now))
+            .skip(start as usize)
+            .take(limit.unwrap_or(50) as usize)
+            .map(|(account_id, grant)| WhitelistEntry { account_id, grant })
+            .collect()
+    }
+}
+
+impl Contract {
+    //check a mint against the whitelist grant of the account invoking the mint function.
+    //The contract owner needs no grant.
+    pub(crate) fn internal_check_grant(&self, prepared: &PreparedMint) -> Result<(), String> {
+        let minter_id = env::signer_account_id();
+        if minter_id == self.owner_id {
+            return Ok(());
+        }
+        let grant = match self.whitelist.get(&minter_id) {
+            Some(grant) => grant,
+            None => return Err("Only whitelisted accounts can call this function".to_string()),
+        };
+        if grant.uses == 0 {
+            return Err("Whitelist error: your grant is used up".to_string());
+        }
+        if !grant.is_active(env::block_timestamp()) {
+            return Err("Whitelist error: your grant has expired".to_string());
+        }
+        if grant.receiver_id != prepared.token.owner_id {
+            return Err(format!("Whitelist error: your grant only allows minting to {}", grant.receiver_id));
+        }
+        if let Some(media_hash) = &grant.media_hash {
+            if media_hash.0 != prepared.media_hash {
+                return Err("Whitelist error: your grant doesn't allow minting this media".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    //consume one use of the whitelist grant of the account invoking the mint function
+    pub(crate) fn internal_use_grant(&mut self) {
+        let minter_id = env::signer_account_id();
+        if minter_id == self.owner_id {
+            return;
+        }
+        if let Some(mut grant) = self.whitelist.get(&minter_id) {
+            grant.uses -= 1;
+            self.whitelist.insert(&minter_id, &grant);
+        }
+    }
+
+    //remove the whitelist grant of the account invoking the mint function if it's used up.
+    //Called once the minter paid for the storage of their tokens, so that the storage
+    //released by the grant is refunded to the owner only.
+    pub(crate) fn internal_remove_used_grant(&mut self) {
+        let minter_id = env::signer_account_id();
+        if self.whitelist.get(&minter_id).map_or(false, |grant| grant.uses == 0) {
+            self.internal_remove_grant(&minter_id);
+        }
+    }
+
+    //remove a grant from the whitelist and refund the storage it released to the contract owner
+    pub(crate) fn internal_remove_grant(&mut self, account_id: &AccountId) -> bool {
+        let initial_storage_usage = env::storage_usage();
+        if self.whitelist.remove(account_id).is_none() {
+            return false;
+        }
+        refund_released_storage(&self.owner_id, initial_storage_usage - env::storage_usage());
+        true
     }
-}
\ No newline at end of file
+}
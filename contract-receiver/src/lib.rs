@@ -0,0 +1,174 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::{env, log, near_bindgen, AccountId, PanicOnDefault, PromiseOrValue};
+
+/*
+Reference receiver contract, used to test the nft_transfer_call() and nft_approve() flows
+of the NFT minter against a live counterpart.
+What the receiver does is picked by the 'msg' passed along with the call:
+ - "accept":       keep the token (nft_on_transfer returns false)
+ - "return":       send the token back to its previous owner (nft_on_transfer returns true)
+ - "accept_later": keep the token, answering through a promise rather than a value
+ - "return_later": send the token back, answering through a promise rather than a value
+ - "panic":        fail the call
+ - "out_of_gas":   burn all the gas attached to the call
+nft_on_approve() understands "accept", "panic" and "out_of_gas".
+Any other message fails the call.
+ */
+
+pub type TokenId = String;
+
+#[near_bindgen]
+#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
+pub struct Contract {
+    //the NFT contract this receiver accepts calls from
+    pub nft_contract_id: AccountId,
+}
+
+#[near_bindgen]
+impl Contract {
+    //initialization function (can only be called once)
+    #[init]
+    pub fn new(nft_contract_id: AccountId) -> Self {
+        Self { nft_contract_id }
+    }
+
+    //called by the NFT contract during nft_transfer_call(), after the token was transferred to this contract.
+    //Returns `true` if the token should be returned back to its previous owner.
+    pub fn nft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        previous_owner_id: AccountId,
+        token_id: TokenId,
+        msg: String,
+    ) -> PromiseOrValue<bool> {
+        self.assert_called_by_nft_contract();
+        log!(
+            "nft_on_transfer of token {} from @{} sent by @{} with msg '{}'",
+            token_id,
+            previous_owner_id,
+            sender_id,
+            msg
+        );
+
+        match msg.as_str() {
+            "accept" => PromiseOrValue::Value(false),
+            "return" => PromiseOrValue::Value(true),
+            "accept_later" => Self::ext(env::current_account_id()).ok_go(false).into(),
+            "return_later" => Self::ext(env::current_account_id()).ok_go(true).into(),
+            "panic" => env::panic_str("nft_on_transfer panicked as requested"),
+            "out_of_gas" => burn_gas(),
+            _ => env::panic_str("Unknown msg"),
+        }
+    }
+
+    //called by the NFT contract during nft_approve() when a msg is passed along
+    pub fn nft_on_approve(
+        &mut self,
+        token_id: TokenId,
+        owner_id: AccountId,
+        approval_id: u64,
+        msg: String,
+    ) -> String {
+        self.assert_called_by_nft_contract();
+        log!(
+            "nft_on_approve of token {} owned by @{} with approval ID {} and msg '{}'",
+            token_id,
+            owner_id,
+            approval_id,
+            msg
+        );
+
+        match msg.as_str() {
+            "accept" => "approved".to_string(),
+            "panic" => env::panic_str("nft_on_approve panicked as requested"),
+            "out_of_gas" => burn_gas(),
+            _ => env::panic_str("Unknown msg"),
+        }
+    }
+
+    //answer of the "_later" messages, returned to the NFT contract through a promise
+    #[private]
+    pub fn ok_go(&self, return_it: bool) -> bool {
+        log!("ok_go: returning {}", return_it);
+        return_it
+    }
+}
+
+impl Contract {
+    //Internal method to verify the predecessor was the NFT contract
+    fn assert_called_by_nft_contract(&self) {
+        assert_eq!(
+            &env::predecessor_account_id(),
+            &self.nft_contract_id,
+            "This function can only be called by the NFT contract"
+        );
+    }
+}
+
+//keep hashing until the call runs out of gas
+fn burn_gas() -> ! {
+    let mut hash = env::sha256(b"out of gas");
+    loop {
+        hash = env::sha256(&hash);
+    }
+}
+
+/* unit tests */
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    fn account(account_name: &str) -> AccountId {
+        account_name.to_string().try_into().unwrap()
+    }
+
+    //set up a contract receiving calls from the NFT contract
+    fn setup() -> Contract {
+        let context = VMContextBuilder::new()
+            .current_account_id(account("receiver.testnet"))
+            .predecessor_account_id(account("nft.testnet"))
+            .build();
+        testing_env!(context);
+        Contract::new(account("nft.testnet"))
+    }
+
+    fn on_transfer(contract: &mut Contract, msg: &str) -> PromiseOrValue<bool> {
+        contract.nft_on_transfer(
+            account("doomslug.testnet"),
+            account("doomslug.testnet"),
+            "001".to_string(),
+            msg.to_string(),
+        )
+    }
+
+    #[test]
+    fn test_on_transfer() {
+        let mut contract = setup();
+        assert!(matches!(on_transfer(&mut contract, "accept"), PromiseOrValue::Value(false)));
+        assert!(matches!(on_transfer(&mut contract, "return"), PromiseOrValue::Value(true)));
+        assert!(matches!(on_transfer(&mut contract, "return_later"), PromiseOrValue::Promise(_)));
+        assert_eq!(
+            contract.nft_on_approve("001".to_string(), account("doomslug.testnet"), 0, "accept".to_string()),
+            "approved"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "nft_on_transfer panicked as requested")]
+    fn test_on_transfer_panic() {
+        let mut contract = setup();
+        on_transfer(&mut contract, "panic");
+    }
+
+    #[test]
+    #[should_panic(expected = "This function can only be called by the NFT contract")]
+    fn test_on_transfer_from_another_contract() {
+        let mut contract = setup();
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(account("other.testnet"))
+            .build());
+        on_transfer(&mut contract, "accept");
+    }
+}
@@ -0,0 +1,198 @@
+use near_sdk::{env, near_bindgen, AccountId, CurveType, PublicKey};
+use near_sdk::json_types::{Base64VecU8, U64};
+use ed25519_dalek::Verifier;
+use std::convert::TryFrom;
+use crate::*;
+
+/*
+ * Threshold attestations.
+ * Lenders usually only need to know that a score clears a cutoff. Rather than storing the score,
+ * the oracle backend signs an attestation such as "account >= 650 as of T" with an ed25519 key
+ * registered on the contract by the owner. Anyone can submit it (paying for its storage): the
+ * contract checks the signature and records it as a typed claim of the account.
+ * The signed message is the borsh serialization of the tuple (contract_account_id, attestation),
+ * so an attestation can't be submitted to another contract.
+ * Claims expire once the validity period set by the owner has elapsed since their 'as of' time.
+ */
+
+// how long a claim holds after its 'as of' time, unless the owner sets another validity
+pub(crate) const DEFAULT_ATTESTATION_VALIDITY: u64 = 30 * NANOS_PER_DAY;
+// max number of claims an account holds at once
+const MAX_CLAIMS_PER_USER: usize = 24;
+
+// an attestation signed by the oracle backend
+#[derive(BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ThresholdAttestation {
+    // the account the attestation is about
+    pub account_id: AccountId,
+    // the score of the account is at least this threshold
+    pub threshold: u16,
+    // when the score cleared the threshold, Unix epoch in nanoseconds
+    pub as_of: U64,
+}
+
+// what a claim states about an account
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub enum ClaimKind {
+    // the score of the account is at least the threshold
+    ScoreAtLeast { threshold: u16 },
+}
+
+// a claim recorded from an attestation, next to the scores of the account
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Claim {
+    pub kind: ClaimKind,
+    pub as_of: U64,
+    // when the claim stops holding, Unix epoch in nanoseconds
+    pub expires_at: U64,
+}
+
+impl Claim {
+    // whether the claim states that the score is at least the given threshold at the given timestamp
+    fn proves_threshold(&self, threshold: u16, timestamp: u64) -> bool {
+        match self.kind {
+            ClaimKind::ScoreAtLeast { threshold: attested } => attested >= threshold && timestamp < self.expires_at.0,
+        }
+    }
+}
+
+// check an ed25519 signature of a message against a NEAR public key
+fn verify_ed25519(public_key: &PublicKey, message: &[u8], signature: &[u8]) -> bool {
+    // the first byte of a NEAR public key is its curve type
+    let public_key = match ed25519_dalek::PublicKey::from_bytes(&public_key.as_bytes()[1..]) {
+        Ok(public_key) => public_key,
+        Err(_) => return false,
+    };
+    match ed25519_dalek::Signature::try_from(signature) {
+        Ok(signature) => public_key.verify(message, &signature).is_ok(),
+        Err(_) => false,
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    // record the claim of an attestation signed by the registered attestation signer.
+    // Anyone can submit an attestation, attaching enough NEAR to cover the storage of the claim.
+    // A claim replaces the claims of the account about the same threshold, unless one of them
+    // is more recent: replaying an older attestation can't shorten the expiry of a claim
+    #[payable]
+    pub fn submit_threshold_attestation(&mut self, attestation: ThresholdAttestation, signature: Base64VecU8) -> Claim {
+        let initial_storage_usage = env::storage_usage();
+
+        // ATTESTATION CHECKS
+        let signer = self
+            .attestation_signer
+            .as_ref()
+            .unwrap_or_else(|| env::panic_str("Attestation error: no attestation signer is registered on the contract"));
+        let message = (env::current_account_id(), &attestation).try_to_vec().unwrap();
+        assert!(
+            verify_ed25519(signer, &message, &signature.0),
+            "Attestation error: invalid signature"
+        );
+        let now = env::block_timestamp();
+        assert!(attestation.as_of.0 <= now, "Attestation error: the attestation is dated in the future");
+        let claim = Claim {
+            kind: ClaimKind::ScoreAtLeast { threshold: attestation.threshold },
+            as_of: attestation.as_of,
+            expires_at: U64(attestation.as_of.0 + self.attestation_validity),
+        };
+        assert!(now < claim.expires_at.0, "Attestation error: the attestation has expired");
+
+        // drop the expired claims and the claims this one replaces
+        let mut claims: Vec<Claim> = self
+            .claims
+            .get(&attestation.account_id)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|old| now < old.expires_at.0)
+            .collect();
+        assert!(
+            !claims.iter().any(|old| old.kind == claim.kind && old.as_of.0 > claim.as_of.0),
+            "Attestation error: a more recent attestation is already recorded"
+        );
+        claims.retain(|old| old.kind != claim.kind);
+        assert!(
+            claims.len() < MAX_CLAIMS_PER_USER,
+            "Attestation error: the account already holds {} claims",
+            MAX_CLAIMS_PER_USER
+        );
+        claims.push(claim.clone());
+        self.claims.insert(&attestation.account_id, &claims);
+
+        // dropping claims may release more storage than the new claim uses
+        let storage_used = env::storage_usage().saturating_sub(initial_storage_usage);
+        charge_storage(storage_used);
+
+        claim
+    }
+
+    // check whether the score of an account is known to be at least the given threshold,
+    // either from an unexpired claim or from its latest score, if stored within the attestation validity
+    pub fn meets_threshold(&self, account_id: AccountId, threshold: u16) -> bool {
+        let now = env::block_timestamp();
+        let claimed = self
+            .claims
+            .get(&account_id)
+            .map_or(false, |claims| claims.iter().any(|claim| claim.proves_threshold(threshold, now)));
+        claimed || self.records.get(&account_id.to_string()).map_or(false, |history| {
+            history
+                .len()
+                .checked_sub(1)
+                .and_then(|index| history.get(index))
+                .map_or(false, |latest| {
+                    latest.score >= threshold && now < latest.timestamp + self.attestation_validity
+                })
+        })
+    }
+
+    // query the unexpired claims of an account
+    pub fn claims(&self, account_id: AccountId) -> Vec<Claim> {
+        let now = env::block_timestamp();
+        self.claims
+            .get(&account_id)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|claim| now < claim.expires_at.0)
+            .collect()
+    }
+
+    // query the public key attestations must be signed with
+    pub fn attestation_signer(&self) -> Option<PublicKey> {
+        self.attestation_signer.clone()
+    }
+
+    // query how long (in nanoseconds) claims hold after their 'as of' time
+    pub fn attestation_validity(&self) -> U64 {
+        U64(self.attestation_validity)
+    }
+
+    // register the ed25519 public key attestations must be signed with.
+    // Passing None stops the contract from accepting attestations.
+    // This method can be called only by the smart contract owner.
+    pub fn set_attestation_signer(&mut self, public_key: Option<PublicKey>) {
+        // ensure the function was called by the smart contract owner, else panic
+        self.assert_called_by_owner();
+
+        if let Some(public_key) = &public_key {
+            assert!(
+                public_key.curve_type() == CurveType::ED25519,
+                "The attestation signer must be an ed25519 public key"
+            );
+        }
+        self.attestation_signer = public_key;
+    }
+
+    // set how long (in nanoseconds) claims hold after their 'as of' time.
+    // It applies to the claims recorded from now on.
+    // This method can be called only by the smart contract owner.
+    pub fn set_attestation_validity(&mut self, validity: U64) {
+        // ensure the function was called by the smart contract owner, else panic
+        self.assert_called_by_owner();
+
+        assert!(validity.0 > 0, "The attestation validity must be greater than zero");
+        self.attestation_validity = validity.0;
+    }
+}
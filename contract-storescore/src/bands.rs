@@ -0,0 +1,125 @@
+use near_sdk::{env, near_bindgen};
+use crate::*;
+
+/*
+ * Score bands.
+ * The owner manages the table mapping score ranges to labels (e.g. "Very Poor" to "Excellent") and tier ids,
+ * so that every client classifies scores the same way. Bands are contiguous: each one starts at its 'min'
+ * and ends where the next one starts, and the first one starts from 0.
+ * Every stored score records the band in force when it was written, so changing the table doesn't
+ * rewrite history. The bands are kept next to the score records, by (account, index), so that the
+ * layout of the records doesn't change; the entries stored before bands were recorded are classified
+ * with the current table. The NFT contract reads the table through score_bands() to label its score cards.
+ */
+
+// max number of bands in the table
+const MAX_SCORE_BANDS: usize = 20;
+// max length of the label of a band, in bytes
+const MAX_BAND_LABEL_LEN: usize = 32;
+
+// an entry of the band table
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BandEntry {
+    // lowest score of the band
+    pub min: u16,
+    pub label: String,
+    pub tier: u8,
+}
+
+// the band of a score, as recorded with every stored score
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ScoreBand {
+    pub min: u16,
+    // upper bound of the band (excluded), None for the last band
+    pub max: Option<u16>,
+    pub label: String,
+    pub tier: u8,
+}
+
+// the bands in force when the contract is initialized
+pub(crate) fn default_score_bands() -> Vec<BandEntry> {
+    [(0, "Very Poor"), (500, "Poor"), (580, "Fair"), (670, "Good"), (740, "Very Good"), (800, "Excellent")]
+        .iter()
+        .enumerate()
+        .map(|(index, (min, label))| BandEntry {
+            min: *min,
+            label: label.to_string(),
+            tier: index as u8 + 1,
+        })
+        .collect()
+}
+
+#[near_bindgen]
+impl Contract {
+    // query the band table
+    pub fn score_bands(&self) -> Vec<BandEntry> {
+        self.score_bands.clone()
+    }
+
+    // query the band of an arbitrary score under the current band table
+    pub fn classify_score(&self, score: u16) -> ScoreBand {
+        let index = self.band_index(score);
+        let entry = &self.score_bands[index];
+        ScoreBand {
+            min: entry.min,
+            max: self.score_bands.get(index + 1).map(|next| next.min),
+            label: entry.label.clone(),
+            tier: entry.tier,
+        }
+    }
+
+    // replace the band table, whose bands must be in ascending order starting from 0,
+    // and rebuild the histogram of the score statistics.
+    // The scores already stored keep the band they were written with.
+    // This method can be called only by the smart contract owner.
+    pub fn set_score_bands(&mut self, bands: Vec<BandEntry>) {
+        // ensure the function was called by the smart contract owner, else panic
+        self.assert_called_by_owner();
+
+        assert!(
+            !bands.is_empty() && bands.len() <= MAX_SCORE_BANDS,
+            "There must be between 1 and {} score bands",
+            MAX_SCORE_BANDS
+        );
+        assert_eq!(bands[0].min, 0, "The first score band must start from 0");
+        assert!(
+            bands.windows(2).all(|pair| pair[0].min < pair[1].min),
+            "The score bands must be in ascending order"
+        );
+        for band in bands.iter() {
+            if band.label.is_empty() || band.label.len() > MAX_BAND_LABEL_LEN {
+                env::panic_str(&format!(
+                    "The label of a score band must hold between 1 and {} bytes",
+                    MAX_BAND_LABEL_LEN
+                ))
+            }
+        }
+
+        self.score_bands = bands;
+        self.rebuild_score_histogram();
+    }
+}
+
+impl Contract {
+    // index of the band of the given score in the band table
+    pub(crate) fn band_index(&self, score: u16) -> usize {
+        // bands start from 0, so every score has a band
+        self.score_bands.iter().rposition(|band| band.min <= score).unwrap()
+    }
+
+    // record the band in force for an entry of a user's score history
+    pub(crate) fn record_entry_band(&mut self, account_id: &AccountId, index: u64, score: u16) {
+        let band = self.classify_score(score);
+        self.entry_bands.insert(&(account_id.clone(), index), &band);
+    }
+
+    // the band recorded for an entry of a user's score history,
+    // or its band in the current table if it was stored before bands were recorded
+    pub(crate) fn entry_band(&self, account_id: &AccountId, index: u64, score: u16) -> ScoreBand {
+        self.entry_bands
+            .get(&(account_id.clone(), index))
+            .unwrap_or_else(|| self.classify_score(score))
+    }
+}
@@ -0,0 +1,128 @@
+use near_sdk::{env, log, near_bindgen, AccountId, Gas, Promise};
+use near_sdk::collections::Vector;
+use near_sdk::json_types::Base64VecU8;
+use near_sdk::serde_json::json;
+use crate::*;
+
+/*
+ * Commit-reveal score storage.
+ * In privacy mode the oracle doesn't store the score of a user but a commitment to it:
+ *   sha256(score ‖ salt ‖ account_id)
+ * where score is the u16 score in little-endian bytes, salt is a random secret of at least 16 bytes
+ * handed to the user off-chain, and account_id is the UTF-8 account id of the user.
+ * Commitments follow the same rules as scores (one every 30 days, at most 24 per user).
+ * The score stays secret until the user discloses it, either to anyone through the
+ * verify_score_commitment() view, or to a verifier contract with reveal_score_commitment().
+ */
+
+// gas attached to the on_score_revealed() call made to the verifier contract
+const GAS_FOR_ON_SCORE_REVEALED: Gas = Gas(10_000_000_000_000);
+// shorter salts would let anyone find the score by trying every salt
+const MIN_SALT_LEN: usize = 16;
+
+// a commitment to a score, stored on-chain in place of the score
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ScoreCommitment {
+    // sha256(score ‖ salt ‖ account_id)
+    pub commitment: Base64VecU8,
+    pub timestamp: u64, // Unix epoch in nanoseconds
+}
+
+// the commitment to a score, as computed off-chain by the oracle and checked on reveal
+pub fn score_commitment_hash(account_id: &AccountId, score: u16, salt: &[u8]) -> Vec<u8> {
+    let mut preimage = score.to_le_bytes().to_vec();
+    preimage.extend_from_slice(salt);
+    preimage.extend_from_slice(account_id.as_bytes());
+    env::sha256(&preimage)
+}
+
+#[near_bindgen]
+impl Contract {
+    // store a commitment to a new score instead of the score itself.
+    // Just like store_score(), it can only be invoked either by the contract owner or by a whitelisted
+    // account id, and it records the commitment under the account of the caller
+    #[payable]
+    pub fn store_score_commitment(&mut self, commitment: Base64VecU8) -> ScoreOutcome {
+        assert_eq!(commitment.0.len(), 32, "Hash error: the commitment must be a sha256 hash");
+
+        //WHITELIST CHECK
+        self.use_grant(None);
+
+        //QUOTA CHECK
+        self.use_submission_quota(&env::signer_account_id());
+
+        let account_id = env::signer_account_id();
+        let new_commitment = ScoreCommitment {
+            commitment,
+            timestamp: env::block_timestamp(),
+        };
+
+        let mut commitments = self.commitments.get(&account_id).unwrap_or_else(|| {
+            log!("{} is a new user", account_id);
+            Vector::new(StorageKey::CommitmentsInner { account_hash: env::sha256(account_id.as_bytes()) })
+        });
+        if let Some(latest) = commitments.len().checked_sub(1).and_then(|index| commitments.get(index)) {
+            if commitments.len() >= MAXOUT_USER_SCORES
+                || new_commitment.timestamp - latest.timestamp <= SCORE_COOLDOWN
+            {
+                env::panic_str("Exceeded score upperbound OR latest score is too recent")
+            }
+        }
+        commitments.push(&new_commitment);
+        self.commitments.insert(&account_id, &commitments);
+        log!("Score commitment stored successfully to NEAR blockchain");
+
+        ScoreOutcome {
+            gas_used: env::used_gas(),
+            score_owner: account_id.to_string(),
+            successful_operation: true,
+        }
+    }
+
+    // query all score commitments of a user
+    pub fn query_score_commitments(&self, account_id: AccountId) -> Vec<ScoreCommitment> {
+        self.commitments
+            .get(&account_id)
+            .map_or(vec![], |commitments| commitments.to_vec())
+    }
+
+    // check whether score and salt open the commitment at the given index of the user's commitments
+    pub fn verify_score_commitment(&self, account_id: AccountId, index: u64, score: u16, salt: Base64VecU8) -> bool {
+        self.check_score_commitment(&account_id, index, score, &salt.0).is_some()
+    }
+
+    // disclose one of the caller's committed scores to a verifier contract:
+    // the opening is checked, then the contract calls on_score_revealed(account_id, score, timestamp)
+    // on the verifier, which can trust the score since it comes from this contract
+    pub fn reveal_score_commitment(&mut self, index: u64, score: u16, salt: Base64VecU8, verifier_id: AccountId) -> Promise {
+        let account_id = env::predecessor_account_id();
+        let commitment = self
+            .check_score_commitment(&account_id, index, score, &salt.0)
+            .unwrap_or_else(|| env::panic_str("Reveal error: the score and salt don't open this commitment"));
+
+        let args = json!({
+            "account_id": account_id,
+            "score": score,
+            "timestamp": commitment.timestamp,
+        })
+        .to_string()
+        .into_bytes();
+        Promise::new(verifier_id).function_call("on_score_revealed".to_string(), args, 0, GAS_FOR_ON_SCORE_REVEALED)
+    }
+}
+
+impl Contract {
+    // the commitment at the given index of the user's commitments, if score and salt open it
+    fn check_score_commitment(&self, account_id: &AccountId, index: u64, score: u16, salt: &[u8]) -> Option<ScoreCommitment> {
+        if salt.len() < MIN_SALT_LEN {
+            return None;
+        }
+        let commitment = self.commitments.get(account_id)?.get(index)?;
+        if commitment.commitment.0 == score_commitment_hash(account_id, score, salt) {
+            Some(commitment)
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,227 @@
+use near_sdk::{env, near_bindgen, AccountId};
+use near_sdk::json_types::U64;
+use crate::*;
+
+/*
+ * Score disputes.
+ * A user who believes one of their scores is wrong files a dispute against its index in their
+ * score history, with a reason. The contract owner then resolves it:
+ * - upheld: the entry is amended with the corrected score, and the original entry is kept in the audit log
+ * - rejected: the entry stays as it is
+ * Every entry can be disputed once, and every step logs an event.
+ */
+
+// max length of the reason of a dispute and of the note of its resolution, in bytes
+const MAX_DISPUTE_TEXT_LEN: usize = 280;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub enum DisputeStatus {
+    Open,
+    Upheld,
+    Rejected,
+}
+
+// a dispute filed by a user against one of their scores
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Dispute {
+    pub reason: String,
+    // when the dispute was filed, Unix epoch in nanoseconds
+    pub filed_at: U64,
+    pub status: DisputeStatus,
+    // who resolved the dispute, when, and why
+    pub resolver_id: Option<AccountId>,
+    pub resolved_at: Option<U64>,
+    pub note: Option<String>,
+}
+
+// an entry amended by an upheld dispute, as recorded in the audit log
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ScoreAmendment {
+    pub account_id: AccountId,
+    pub index: u64,
+    // the entry before and after the amendment
+    pub original: Score,
+    pub corrected: Score,
+    pub resolver_id: AccountId,
+    // when the entry was amended, Unix epoch in nanoseconds
+    pub amended_at: U64,
+}
+
+#[near_bindgen]
+impl Contract {
+    // dispute the entry at the given index of the caller's score history
+    pub fn file_dispute(&mut self, index: u64, reason: String) -> Dispute {
+        let account_id = env::predecessor_account_id();
+        assert!(
+            !reason.is_empty() && reason.len() <= MAX_DISPUTE_TEXT_LEN,
+            "Dispute error: the reason must hold between 1 and {} bytes",
+            MAX_DISPUTE_TEXT_LEN
+        );
+        let entry = self.score_entry(&account_id, index);
+        assert!(
+            !self.disputes.contains_key(&(account_id.clone(), index)),
+            "Dispute error: this score was already disputed"
+        );
+
+        let dispute = Dispute {
+            reason: reason.clone(),
+            filed_at: U64(env::block_timestamp()),
+            status: DisputeStatus::Open,
+            resolver_id: None,
+            resolved_at: None,
+            note: None,
+        };
+        self.disputes.insert(&(account_id.clone(), index), &dispute);
+
+        // LOG EVENTS
+        log_event(EventLogVariant::ScoreDisputeFiled(vec![ScoreDisputeFiledLog {
+            account_id: account_id.to_string(),
+            index,
+            reason,
+        }]));
+        log!("{} disputed their score of {}", account_id, entry.score);
+
+        dispute
+    }
+
+    // uphold an open dispute: amend the entry with the corrected score and description,
+    // keeping its timestamp, and record the original entry in the audit log.
+    // This method can be called only by the smart contract owner.
+    pub fn uphold_dispute(
+        &mut self,
+        account_id: AccountId,
+        index: u64,
+        corrected_score: u16,
+        corrected_description: String,
+        note: String,
+    ) -> Dispute {
+        // ensure the function was called by the smart contract owner, else panic
+        self.assert_called_by_owner();
+
+        let original = self.score_entry(&account_id, index);
+        let dispute = self.resolve_dispute(&account_id, index, DisputeStatus::Upheld, note.clone());
+
+        // amend the entry and keep the original one in the audit log
+        let corrected = User {
+            score: corrected_score,
+            timestamp: original.timestamp,
+            description: corrected_description.as_bytes().to_vec(),
+        };
+        let original_entry = self.to_score(&account_id, index, &original);
+        let mut history = self.records.get(&account_id.to_string()).unwrap();
+        history.replace(index, &corrected);
+        self.record_entry_band(&account_id, index, corrected_score);
+        self.score_audit_log.push(&ScoreAmendment {
+            account_id: account_id.clone(),
+            index,
+            original: original_entry,
+            corrected: self.to_score(&account_id, index, &corrected),
+            resolver_id: env::predecessor_account_id(),
+            amended_at: U64(env::block_timestamp()),
+        });
+
+        // the statistics and the subscribers follow the latest score of the user
+        if index + 1 == history.len() {
+            self.update_score_stats(&account_id, Some(original.score), Some(corrected_score));
+            self.notify_subscribers(&account_id, &corrected);
+        }
+
+        // LOG EVENTS
+        log_event(EventLogVariant::ScoreDisputeUpheld(vec![ScoreDisputeResolvedLog {
+            account_id: account_id.to_string(),
+            index,
+            resolver_id: env::predecessor_account_id().to_string(),
+            original_score: original.score,
+            corrected_score: Some(corrected_score),
+            note,
+        }]));
+
+        dispute
+    }
+
+    // reject an open dispute, leaving the entry as it is.
+    // This method can be called only by the smart contract owner.
+    pub fn reject_dispute(&mut self, account_id: AccountId, index: u64, note: String) -> Dispute {
+        // ensure the function was called by the smart contract owner, else panic
+        self.assert_called_by_owner();
+
+        let entry = self.score_entry(&account_id, index);
+        let dispute = self.resolve_dispute(&account_id, index, DisputeStatus::Rejected, note.clone());
+
+        // LOG EVENTS
+        log_event(EventLogVariant::ScoreDisputeRejected(vec![ScoreDisputeResolvedLog {
+            account_id: account_id.to_string(),
+            index,
+            resolver_id: env::predecessor_account_id().to_string(),
+            original_score: entry.score,
+            corrected_score: None,
+            note,
+        }]));
+
+        dispute
+    }
+
+    // query the dispute filed against the entry at the given index of a user's score history, if any
+    pub fn dispute_status(&self, account_id: AccountId, index: u64) -> Option<Dispute> {
+        self.disputes.get(&(account_id, index))
+    }
+
+    // query the entries amended by upheld disputes, using pagination
+    pub fn score_audit_log(&self, from_index: Option<u64>, limit: Option<u64>) -> Vec<ScoreAmendment> {
+        self.score_audit_log
+            .iter()
+            .skip(from_index.unwrap_or(0) as usize)
+            .take(limit.unwrap_or(50) as usize)
+            .collect()
+    }
+}
+
+impl Contract {
+    // the entry at the given index of a user's score history, else panic
+    fn score_entry(&self, account_id: &AccountId, index: u64) -> User {
+        self.records
+            .get(&account_id.to_string())
+            .and_then(|history| history.get(index))
+            .unwrap_or_else(|| env::panic_str("Dispute error: this score doesn't exist"))
+    }
+
+    // close an open dispute with the given status
+    fn resolve_dispute(&mut self, account_id: &AccountId, index: u64, status: DisputeStatus, note: String) -> Dispute {
+        assert!(
+            note.len() <= MAX_DISPUTE_TEXT_LEN,
+            "Dispute error: the note must hold at most {} bytes",
+            MAX_DISPUTE_TEXT_LEN
+        );
+        let key = (account_id.clone(), index);
+        let mut dispute = self
+            .disputes
+            .get(&key)
+            .unwrap_or_else(|| env::panic_str("Dispute error: this score isn't disputed"));
+        assert_eq!(dispute.status, DisputeStatus::Open, "Dispute error: this dispute was already resolved");
+
+        dispute.status = status;
+        dispute.resolver_id = Some(env::predecessor_account_id());
+        dispute.resolved_at = Some(U64(env::block_timestamp()));
+        dispute.note = Some(note);
+        self.disputes.insert(&key, &dispute);
+        dispute
+    }
+}
+
+// log a contract-specific event
+fn log_event(event: EventLogVariant) {
+    let log: EventLog = EventLog {
+        // standard name ("nearoracle")
+        standard: ORACLE_STANDARD_NAME.to_string(),
+        // version of the standard ("1.0.0")
+        version: ORACLE_EVENT_VERSION.to_string(),
+        // the data related with the event stored in a vector
+        event,
+    };
+
+    // log the serialized json
+    env::log_str(&log.to_string());
+}
@@ -0,0 +1,80 @@
+use std::fmt;
+use near_sdk::serde::{Serialize, Deserialize};
+
+/// Enum that represents the data type of the EventLog.
+/// The events are contract-specific (not part of any NEP) and
+/// cover every step of a score dispute (ScoreDisputeFiled, ScoreDisputeUpheld, ScoreDisputeRejected).
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "event", content = "data")]
+#[serde(rename_all = "snake_case")]
+#[serde(crate = "near_sdk::serde")]
+#[non_exhaustive]
+pub enum EventLogVariant {
+    ScoreDisputeFiled(Vec<ScoreDisputeFiledLog>),
+    ScoreDisputeUpheld(Vec<ScoreDisputeResolvedLog>),
+    ScoreDisputeRejected(Vec<ScoreDisputeResolvedLog>),
+}
+
+/*
+ * Interface to capture data about an event
+ *
+ * Arguments:
+ * `standard`: name of standard e.g. nearoracle
+ * `version`: e.g. 1.0.0
+ * `event`: associate event data
+ */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct EventLog {
+    pub standard: String,
+    pub version: String,
+
+    // `flatten` to not have "event": {<EventLogVariant>} in the JSON,
+    // just have the contents of {<EventLogVariant>}
+    #[serde(flatten)]
+    pub event: EventLogVariant,
+}
+
+impl fmt::Display for EventLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_fmt(format_args!(
+            "EVENT_JSON:{}",
+            &serde_json::to_string(self).map_err(|_| fmt::Error)?
+        ))
+    }
+}
+
+/// An event log to capture a user disputing one of their scores
+///
+/// Arguments
+/// * `account_id`: "user.near"
+/// * `index`: index of the disputed entry in the user's score history
+/// * `reason`: why the user disputes the score
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ScoreDisputeFiledLog {
+    pub account_id: String,
+    pub index: u64,
+    pub reason: String,
+}
+
+/// An event log to capture the resolution of a score dispute
+///
+/// Arguments
+/// * `account_id`: "user.near"
+/// * `index`: index of the disputed entry in the user's score history
+/// * `resolver_id`: "oracle.near"
+/// * `original_score`: the score of the entry when the dispute was filed
+/// * `corrected_score`: the score the entry was amended to, if the dispute was upheld
+/// * `note`: why the dispute was upheld or rejected
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ScoreDisputeResolvedLog {
+    pub account_id: String,
+    pub index: u64,
+    pub resolver_id: String,
+    pub original_score: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub corrected_score: Option<u16>,
+    pub note: String,
+}
@@ -1,17 +1,49 @@
 // Import crates
-use near_sdk::collections::{LookupSet, LookupMap, Vector};
+use near_sdk::collections::{LookupMap, LookupSet, TreeMap, UnorderedMap, Vector};
 use near_sdk::{log,
     borsh::{self, BorshDeserialize, BorshSerialize},
     serde::{Deserialize, Serialize},
-    AccountId, Gas, PanicOnDefault, BorshStorageKey,
+    AccountId, Gas, PanicOnDefault, BorshStorageKey, PublicKey,
 };
 use near_sdk::{env, near_bindgen};
 
 pub use crate::whitelist::*;
+pub use crate::oracle::*;
+pub use crate::subscriptions::*;
+pub use crate::commitments::*;
+pub use crate::attestations::*;
+pub use crate::disputes::*;
+pub use crate::events::*;
+pub use crate::stats::*;
+pub use crate::bands::*;
+pub use crate::quotas::*;
+pub use crate::migrate::*;
 mod whitelist;
+mod oracle;
+mod subscriptions;
+mod commitments;
+mod attestations;
+mod disputes;
+mod events;
+mod stats;
+mod bands;
+mod quotas;
+mod migrate;
+
+// name and version of the contract-specific events (not part of any NEP)
+pub const ORACLE_STANDARD_NAME: &str = "nearoracle";
+pub const ORACLE_EVENT_VERSION: &str = "1.0.0";
 
 const MAXOUT_USER_SCORES: u64 = 24; 
 
+// every timestamp handled by this contract is a Unix epoch in nanoseconds,
+// i.e., the unit returned by env::block_timestamp()
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+const NANOS_PER_DAY: u64 = 86_400 * NANOS_PER_SEC;
+
+// min time between two scores of a user: 30 days (prod) - use 30 * NANOS_PER_SEC in dev
+const SCORE_COOLDOWN: u64 = 30 * NANOS_PER_DAY;
+
 // --------------------------------------------------------------------- //
 //                          Define main objects                          //
 //                                                                       //
@@ -28,7 +60,7 @@ pub struct State {
 #[serde(crate = "near_sdk::serde")]
 pub struct ContractState {
     owner: String,
-    timestamp: u64,
+    timestamp: u64, // Unix epoch in nanoseconds
     size_now: u64,
     user_count: u64,
     score_count: u64,
@@ -48,6 +80,24 @@ pub struct OnChainHistory {
     scores: Vec<User>,
 }
 
+// output of the function summarizing a user's score history
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ScoreSummary {
+    latest: u16,
+    previous: Option<u16>,
+    // latest score minus previous score
+    delta: Option<i32>,
+    // band of the latest score, as recorded when it was stored
+    band: ScoreBand,
+    count: u64,
+    first_timestamp: u64, // Unix epoch in nanoseconds
+    last_timestamp: u64, // Unix epoch in nanoseconds
+    // earliest time the user can store a new score, Unix epoch in nanoseconds
+    // None if the user already holds the max number of scores
+    next_score_at: Option<u64>,
+}
+
 // was the operation of publishing a score to blockchain successful?
 // the struct below describes the operation outcome
 #[derive(Serialize)]
@@ -64,6 +114,22 @@ pub struct ScoreOutcome {
 pub enum StorageKey {
     Accounts { account_hash: Vec<u8> },
     WhiteList,
+    // reserved: formerly used by the whitelist grants map, do not reuse
+    WhitelistGrants,
+    WhitelistEntries,
+    OracleUsage,
+    Subscriptions,
+    Subscribers { account_hash: Vec<u8> },
+    Commitments,
+    CommitmentsInner { account_hash: Vec<u8> },
+    Claims,
+    Disputes,
+    ScoreAuditLog,
+    LatestScoreCounts,
+    SubmissionQuotas,
+    SubmissionCounters,
+    ScoreStatsAccounts,
+    EntryBands,
 }
 
 // user's score, timestamp, and score description as a struct
@@ -71,17 +137,18 @@ pub enum StorageKey {
 #[serde(crate = "near_sdk::serde")]
 pub struct User {
     pub score: u16,
-    pub timestamp: u64,
+    pub timestamp: u64, // Unix epoch in nanoseconds
     pub description: Vec<u8>,
 }
 
 // user's score, timestamp, and score description as an offchain sruct
-#[derive(Serialize)]
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
 #[serde(crate = "near_sdk::serde")]
 pub struct Score {
     pub score: u16,
-    pub timestamp: u64,
+    pub timestamp: u64, // Unix epoch in nanoseconds
     pub description: String,
+    pub band: ScoreBand, // band in force when the score was stored
 }
 
 // this is the singleton = the main struct for this smart contract
@@ -95,7 +162,45 @@ pub struct Contract {
     owner_id: AccountId,
     records: LookupMap<String, Vector<User>>,
     contract_state: State,
-    whitelist: LookupSet<AccountId>,
+    whitelist: UnorderedMap<AccountId, WhitelistGrant>,
+    // fee and rate limit applied to get_score_paid()
+    oracle_config: OracleConfig,
+    // queries made by each rate-limited caller of get_score_paid() in its current window
+    oracle_usage: LookupMap<AccountId, CounterWindow>,
+    // contracts notified of the new scores of an account, or of all accounts (None)
+    subscriptions: LookupMap<Option<AccountId>, UnorderedMap<AccountId, Subscription>>,
+    // score commitments of the users who keep their scores private
+    commitments: LookupMap<AccountId, Vector<ScoreCommitment>>,
+    // public key of the oracle backend signing threshold attestations
+    attestation_signer: Option<PublicKey>,
+    // how long claims hold after their 'as of' time, in nanoseconds
+    attestation_validity: u64,
+    // claims recorded from threshold attestations
+    claims: LookupMap<AccountId, Vec<Claim>>,
+    // disputes filed against the entries of the users' score histories, by (account, index)
+    disputes: LookupMap<(AccountId, u64), Dispute>,
+    // entries amended by upheld disputes, with their original version
+    score_audit_log: Vector<ScoreAmendment>,
+    // table mapping score ranges to labels and tier ids
+    score_bands: Vec<BandEntry>,
+    // band in force when each entry of the users' score histories was written, by (account, index)
+    entry_bands: LookupMap<(AccountId, u64), ScoreBand>,
+    // running statistics over the latest score of every user
+    score_stats: ScoreStats,
+    // number of users whose latest score is a given score
+    latest_score_counts: TreeMap<u16, u64>,
+    // users whose latest score is counted in the statistics
+    score_stats_accounts: LookupSet<AccountId>,
+    // max number of submissions per hour and per day of the oracles without a quota of their own
+    default_submission_quota: SubmissionQuota,
+    // quotas set by the owner for given oracles
+    submission_quotas: LookupMap<AccountId, SubmissionQuota>,
+    // submissions made by each oracle in its current hour and day windows
+    submission_counters: LookupMap<AccountId, SubmissionCounters>,
+    // max number of submissions of all oracles together per hour
+    global_submission_limit: Option<u32>,
+    // submissions made by all oracles in the current hour window
+    global_submissions: CounterWindow,
 }
 
 // --------------------------------------------------------------------- //
@@ -113,15 +218,14 @@ impl Contract {
             !env::state_exists(),
             "The contract is already initialized"
         );
-        Self {
+        Self::with_state(
             owner_id,
-            records: LookupMap::new(b"m"),
-            contract_state: State {
+            LookupMap::new(b"m"),
+            State {
                 user_count: 0u64,
                 score_count: 0u64,
             },
-            whitelist: LookupSet::new(StorageKey::WhiteList.try_to_vec().unwrap()),
-        }
+        )
     }
 
     // -----------------------------------------------------//
@@ -140,13 +244,13 @@ impl Contract {
 
         //WHITELIST CHECK
         //the account invoking the store_score() function must be
-        //either the contract owner or a whitelisted account id
-        if &env::signer_account_id() != &self.owner_id {
-            assert!(
-                self.whitelist.contains(&env::signer_account_id()),
-                "Permission error: the account id that called this function is not whitelisted. Try with another account"
-            );
-        };
+        //either the contract owner or a whitelisted account id,
+        //and the score must be allowed by its grant, which is consumed
+        self.use_grant(Some((score, &description)));
+
+        //QUOTA CHECK
+        //the account is limited to a number of submissions per hour and per day
+        self.use_submission_quota(&env::signer_account_id());
 
         let account_id = String::from(env::signer_account_id());
         let new_score = User {
@@ -172,6 +276,7 @@ impl Contract {
                 if self.records.insert(&account_id, &x).is_some() {
                     self.contract_state.user_count += 1;
                     self.contract_state.score_count += 1;
+                    self.update_score_stats(&env::signer_account_id(), None, Some(new_score.score));
                     success = true;
                     log!("Score stored successfully to NEAR blockchain");
                 }
@@ -185,14 +290,15 @@ impl Contract {
                     let timelapsed = new_score.timestamp - j.timestamp;
                     // if statement w/ 2 conditions: iff there's less than X scores, iff last score is 30+ days old
                     if i.len() < MAXOUT_USER_SCORES
-                        // && timelapsed > 30 * u64::pow(10, 9) { // 30 seconds
-                        && timelapsed > 2592 * u64::pow(10, 12) {  // 30 days
+                        // && timelapsed > 30 * NANOS_PER_SEC { // 30 seconds
+                        && timelapsed > SCORE_COOLDOWN {  // 30 days
                         let mut y = i;
                         y.push(&new_score);
                         // update the score count iff you succeeded writing it to chain
                         self.records.insert(&account_id, &y);
                         if self.records.insert(&account_id, &y).is_some() {
                             self.contract_state.score_count += 1;
+                            self.update_score_stats(&env::signer_account_id(), Some(j.score), Some(new_score.score));
                             success = true;
                             log!("Score stored successfully to NEAR blockchain");
                         }
@@ -205,8 +311,12 @@ impl Contract {
             }
         }
 
-        //remove the account id that called the store_score() function from the contract whitelist
-        self.whitelist.remove(&env::signer_account_id());
+        // record the band of the new entry and notify the contracts subscribed to the account's scores
+        if success {
+            let index = self.maxout_check(account_id.clone()) - 1;
+            self.record_entry_band(&env::signer_account_id(), index, score);
+            self.notify_subscribers(&env::signer_account_id(), &new_score);
+        }
 
         // return an outcome struct describing whether the
         // operation of storing a score to blockchain was successful
@@ -221,14 +331,10 @@ impl Contract {
     pub fn query_score_history(&self, account_id: String) -> MyScoreHistory {
         if let Some(a) = self.records.get(&account_id) {
             
+            let account_id: AccountId = account_id.parse().unwrap();
             let mut score_history = vec![];
-            for i in a.iter() {
-                let s = Score {
-                    score: i.score,
-                    timestamp: i.timestamp,
-                    description: String::from_utf8(i.description).unwrap(), //decrypt message
-                };
-                score_history.push(s);
+            for (index, i) in a.iter().enumerate() {
+                score_history.push(self.to_score(&account_id, index as u64, &i));
             };
             return MyScoreHistory {
                 scores: score_history,
@@ -239,6 +345,32 @@ impl Contract {
         }
     }
 
+    // summarize the score history of a user, or return None if they have no score:
+    // latest and previous scores, their difference, the band of the latest score,
+    // and when the user can store a new score given the cooldown
+    pub fn score_summary(&self, account_id: String) -> Option<ScoreSummary> {
+        let history = self.records.get(&account_id)?;
+        let count = history.len();
+        let latest = history.get(count.checked_sub(1)?)?;
+        let previous = count.checked_sub(2).and_then(|index| history.get(index));
+        let first = history.get(0)?;
+        Some(ScoreSummary {
+            latest: latest.score,
+            previous: previous.as_ref().map(|previous| previous.score),
+            delta: previous.map(|previous| i32::from(latest.score) - i32::from(previous.score)),
+            band: self.entry_band(&account_id.parse().unwrap(), count - 1, latest.score),
+            count,
+            first_timestamp: first.timestamp,
+            last_timestamp: latest.timestamp,
+            // store_score() requires more than SCORE_COOLDOWN to have elapsed since the latest score
+            next_score_at: if count < MAXOUT_USER_SCORES {
+                Some(latest.timestamp + SCORE_COOLDOWN + 1)
+            } else {
+                None
+            },
+        })
+    }
+
     // -----------------------------------------------------//
     //              State-related implementations           //
     // -----------------------------------------------------//
@@ -277,6 +409,49 @@ impl Contract {
     }
 }
 
+impl Contract {
+    // the offchain version of an entry of a user's score history, with its recorded band
+    pub(crate) fn to_score(&self, account_id: &AccountId, index: u64, user: &User) -> Score {
+        Score {
+            score: user.score,
+            timestamp: user.timestamp,
+            description: String::from_utf8(user.description.clone()).unwrap(), //decrypt message
+            band: self.entry_band(account_id, index, user.score),
+        }
+    }
+
+    // build the contract around the given owner, score records and counters,
+    // with every other field in its initial state
+    pub(crate) fn with_state(owner_id: AccountId, records: LookupMap<String, Vector<User>>, contract_state: State) -> Self {
+        let score_bands = default_score_bands();
+        Self {
+            owner_id,
+            records,
+            contract_state,
+            whitelist: UnorderedMap::new(StorageKey::WhitelistEntries.try_to_vec().unwrap()),
+            oracle_config: OracleConfig::default(),
+            oracle_usage: LookupMap::new(StorageKey::OracleUsage),
+            subscriptions: LookupMap::new(StorageKey::Subscriptions),
+            commitments: LookupMap::new(StorageKey::Commitments),
+            attestation_signer: None,
+            attestation_validity: DEFAULT_ATTESTATION_VALIDITY,
+            claims: LookupMap::new(StorageKey::Claims),
+            disputes: LookupMap::new(StorageKey::Disputes),
+            score_audit_log: Vector::new(StorageKey::ScoreAuditLog),
+            score_stats: ScoreStats::new(score_bands.len()),
+            score_bands,
+            entry_bands: LookupMap::new(StorageKey::EntryBands),
+            latest_score_counts: TreeMap::new(StorageKey::LatestScoreCounts),
+            score_stats_accounts: LookupSet::new(StorageKey::ScoreStatsAccounts),
+            default_submission_quota: SubmissionQuota::default(),
+            submission_quotas: LookupMap::new(StorageKey::SubmissionQuotas),
+            submission_counters: LookupMap::new(StorageKey::SubmissionCounters),
+            global_submission_limit: None,
+            global_submissions: CounterWindow { started_at: 0, count: 0 },
+        }
+    }
+}
+
 /*
  * the rest of this file sets up unit tests
  * execute them running the command:
@@ -287,9 +462,10 @@ impl Contract {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use near_sdk::json_types::{Base64VecU8, U128, U64};
     use near_sdk::test_utils::VMContextBuilder;
     use near_sdk::{testing_env, AccountId, VMContext};
-    use std::convert::TryInto;
+    use std::convert::{TryFrom, TryInto};
 
     // define 3 fake users
     fn doomslug() -> AccountId {
@@ -474,7 +650,7 @@ mod tests {
         // call read_state() to check stats
         assert_eq!(1, contract.read_state().user_count, "ERR: expected 1 user");
         assert_eq!(3, contract.read_state().score_count, "ERR: expected 3 scores");
-        assert!(contract.read_state().timestamp < 5 * u64::pow(10, 9), "ERR: contract should execute in < 5 sec");
+        assert!(contract.read_state().timestamp < 5 * NANOS_PER_SEC, "ERR: contract should execute in < 5 sec");
         assert!(contract.read_state().timestamp == env::block_timestamp(), "ERR: timestamp now should be identical to the contract timestamp");
 
         // ensure contract owner and score owner are correct
@@ -482,4 +658,702 @@ mod tests {
         assert_eq!("spensa.testnet", contract.read_state().owner, "ERR: mismatching contract owners");
         assert_eq!("spensa.testnet".to_string(), String::from(contract.owner_id), "ERR: mismatching contract owners");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn store_score_with_grant() {
+        let mut context = get_context(false, spensa());
+        context.attached_deposit = 10u128.pow(23);
+        testing_env!(context.clone());
+        let mut contract = Contract::new(spensa());
+
+        // the owner allows rainbow to store a single, given score
+        let payload = (701u16, "Score of 701".to_string()).try_to_vec().unwrap();
+        contract.add_to_whitelist(&rainbow(), WhitelistGrant {
+            payload_hash: Some(env::sha256(&payload).into()),
+            uses: 1,
+            expires_at: None,
+            reason: "Monthly score".to_string(),
+        });
+
+        // rainbow stores the score, which consumes the grant
+        context.signer_account_id = rainbow();
+        context.predecessor_account_id = rainbow();
+        context.storage_usage = env::storage_usage();
+        testing_env!(context);
+        let out = contract.store_score(701, "Score of 701".to_string());
+        assert!(out.successful_operation);
+        assert!(contract.whitelist_grant(rainbow()).is_none(), "ERR: the grant should be used up");
+    }
+
+    #[test]
+    #[should_panic(expected = "Permission error: your whitelist grant doesn't allow storing this score")]
+    fn store_score_outside_grant() {
+        let mut context = get_context(false, spensa());
+        context.attached_deposit = 10u128.pow(23);
+        testing_env!(context.clone());
+        let mut contract = Contract::new(spensa());
+
+        // the owner allows rainbow to store a score of 701 only
+        let payload = (701u16, "Score of 701".to_string()).try_to_vec().unwrap();
+        contract.add_to_whitelist(&rainbow(), WhitelistGrant {
+            payload_hash: Some(env::sha256(&payload).into()),
+            uses: 1,
+            expires_at: None,
+            reason: "Monthly score".to_string(),
+        });
+
+        // rainbow can't store another score
+        context.signer_account_id = rainbow();
+        context.predecessor_account_id = rainbow();
+        context.storage_usage = env::storage_usage();
+        testing_env!(context);
+        contract.store_score(850, "Score of 850".to_string());
+    }
+
+    #[test]
+    fn whitelist_tickets() {
+        let mut context = get_context(false, spensa());
+        context.attached_deposit = 10u128.pow(23);
+        testing_env!(context.clone());
+        let mut contract = Contract::new(spensa());
+
+        // whitelist rainbow for two scores, and doomslug for a single day
+        let grant = WhitelistGrant {
+            payload_hash: None,
+            uses: 2,
+            expires_at: None,
+            reason: "Score refresh".to_string(),
+        };
+        assert!(contract.add_to_whitelist(&rainbow(), grant.clone()));
+        assert!(contract.add_to_whitelist(&doomslug(), WhitelistGrant {
+            expires_at: Some(U64(NANOS_PER_DAY)),
+            ..grant
+        }));
+        assert_eq!(2, contract.whitelist(None, None).len());
+        assert_eq!(doomslug(), contract.whitelist(Some(U128(1)), Some(1))[0].account_id);
+
+        // the multi-use grant survives its first use
+        context.signer_account_id = rainbow();
+        context.predecessor_account_id = rainbow();
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        contract.store_score(701, "Score of 701".to_string());
+        assert!(contract.is_whitelisted(rainbow()));
+        assert_eq!(1, contract.whitelist_grant(rainbow()).unwrap().uses);
+
+        // once expired, a grant is left out of the views, then pruned by anyone
+        context.block_timestamp = 2 * NANOS_PER_DAY;
+        context.storage_usage = env::storage_usage();
+        testing_env!(context);
+        assert!(!contract.is_whitelisted(doomslug()));
+        assert_eq!(1, contract.whitelist(None, None).len());
+        assert_eq!(1, contract.prune_whitelist(None));
+        assert!(contract.whitelist_grant(doomslug()).is_none());
+    }
+
+    #[test]
+    fn oracle_get_score() {
+        let mut context = get_context(false, spensa());
+        testing_env!(context.clone());
+        let mut contract = Contract::new(spensa());
+
+        // an account without score has none
+        assert!(contract.get_score(rainbow(), ScoreType::Credit, None).is_none());
+
+        // the owner stores its own score, which other contracts can then read
+        context.signer_account_id = spensa();
+        context.block_timestamp = NANOS_PER_DAY;
+        testing_env!(context.clone());
+        contract.store_score(720, "Score of 720".to_string());
+        context.predecessor_account_id = doomslug();
+        context.block_timestamp = 3 * NANOS_PER_DAY;
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        let score = contract.get_score(spensa(), ScoreType::Credit, Some(U64(5 * NANOS_PER_DAY))).unwrap();
+        assert_eq!(720, score.score);
+        assert_eq!(NANOS_PER_DAY, score.timestamp);
+
+        // the score is too old for a caller only accepting scores of the last day
+        assert!(contract.get_score(spensa(), ScoreType::Credit, Some(U64(NANOS_PER_DAY))).is_none());
+    }
+
+    #[test]
+    fn score_commitments() {
+        let mut context = get_context(false, spensa());
+        context.signer_account_id = spensa();
+        context.block_timestamp = NANOS_PER_DAY;
+        testing_env!(context.clone());
+        let mut contract = Contract::new(spensa());
+
+        // the owner commits to its score of 710, which stays secret
+        let salt = Base64VecU8(vec![7; 32]);
+        let commitment = score_commitment_hash(&spensa(), 710, &salt.0);
+        assert!(contract.store_score_commitment(Base64VecU8(commitment.clone())).successful_operation);
+        assert!(!contract.user_exist(spensa().to_string()));
+        let commitments = contract.query_score_commitments(spensa());
+        assert_eq!(1, commitments.len());
+        assert_eq!(commitment, commitments[0].commitment.0);
+        assert_eq!(NANOS_PER_DAY, commitments[0].timestamp);
+
+        // only the right score and salt open the commitment
+        assert!(contract.verify_score_commitment(spensa(), 0, 710, salt.clone()));
+        assert!(!contract.verify_score_commitment(spensa(), 0, 711, salt.clone()));
+        assert!(!contract.verify_score_commitment(spensa(), 1, 710, salt.clone()));
+        assert!(!contract.verify_score_commitment(rainbow(), 0, 710, salt.clone()));
+
+        // salts too short to keep the score secret are rejected
+        let short_salt = Base64VecU8(vec![7; 8]);
+        let weak_commitment = score_commitment_hash(&spensa(), 710, &short_salt.0);
+        context.block_timestamp = 32 * NANOS_PER_DAY;
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        contract.store_score_commitment(Base64VecU8(weak_commitment));
+        assert!(!contract.verify_score_commitment(spensa(), 1, 710, short_salt));
+
+        // the user discloses its score to a verifier contract
+        contract.reveal_score_commitment(0, 710, salt, doomslug());
+        assert!(near_sdk::test_utils::get_created_receipts()
+            .iter()
+            .any(|receipt| receipt.receiver_id.to_string() == doomslug().to_string()));
+    }
+
+    // ed25519 keypair standing in for the oracle backend signing threshold attestations
+    fn attestation_keypair() -> ed25519_dalek::Keypair {
+        let secret = ed25519_dalek::SecretKey::from_bytes(&[7; 32]).unwrap();
+        let public = (&secret).into();
+        ed25519_dalek::Keypair { secret, public }
+    }
+
+    // construct an attestation that the account's score is at least the threshold, signed by the keypair
+    fn signed_attestation(account_id: AccountId, threshold: u16, as_of: u64) -> (ThresholdAttestation, Base64VecU8) {
+        use ed25519_dalek::Signer;
+        let attestation = ThresholdAttestation { account_id, threshold, as_of: U64(as_of) };
+        let message = (env::current_account_id(), &attestation).try_to_vec().unwrap();
+        let signature = attestation_keypair().sign(&message).to_bytes().to_vec();
+        (attestation, Base64VecU8(signature))
+    }
+
+    #[test]
+    fn threshold_attestations() {
+        let mut context = get_context(false, spensa());
+        context.block_timestamp = NANOS_PER_DAY;
+        context.attached_deposit = 10u128.pow(22);
+        testing_env!(context.clone());
+        let mut contract = Contract::new(spensa());
+        let mut public_key = vec![0];
+        public_key.extend_from_slice(attestation_keypair().public.as_bytes());
+        contract.set_attestation_signer(Some(PublicKey::try_from(public_key).unwrap()));
+
+        // anyone can submit an attestation signed by the oracle backend
+        context.predecessor_account_id = doomslug();
+        testing_env!(context.clone());
+        let (attestation, signature) = signed_attestation(rainbow(), 650, NANOS_PER_DAY);
+        let claim = contract.submit_threshold_attestation(attestation, signature);
+        assert_eq!(ClaimKind::ScoreAtLeast { threshold: 650 }, claim.kind);
+        assert_eq!(U64(31 * NANOS_PER_DAY), claim.expires_at);
+        assert_eq!(vec![claim], contract.claims(rainbow()));
+
+        // the claim answers lower thresholds only, and only for the attested account
+        assert!(contract.meets_threshold(rainbow(), 600));
+        assert!(contract.meets_threshold(rainbow(), 650));
+        assert!(!contract.meets_threshold(rainbow(), 700));
+        assert!(!contract.meets_threshold(doomslug(), 600));
+
+        // the claim expires after the attestation validity
+        context.block_timestamp = 31 * NANOS_PER_DAY;
+        testing_env!(context.clone());
+        assert!(!contract.meets_threshold(rainbow(), 600));
+        assert!(contract.claims(rainbow()).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Attestation error: invalid signature")]
+    fn threshold_attestation_tampered() {
+        let mut context = get_context(false, spensa());
+        context.attached_deposit = 10u128.pow(22);
+        testing_env!(context);
+        let mut contract = Contract::new(spensa());
+        let mut public_key = vec![0];
+        public_key.extend_from_slice(attestation_keypair().public.as_bytes());
+        contract.set_attestation_signer(Some(PublicKey::try_from(public_key).unwrap()));
+
+        // raising the threshold of a signed attestation breaks its signature
+        let (mut attestation, signature) = signed_attestation(rainbow(), 650, 0);
+        attestation.threshold = 800;
+        contract.submit_threshold_attestation(attestation, signature);
+    }
+
+    #[test]
+    #[should_panic(expected = "Attestation error: a more recent attestation is already recorded")]
+    fn threshold_attestation_replayed() {
+        let mut context = get_context(false, spensa());
+        context.block_timestamp = 2 * NANOS_PER_DAY;
+        context.attached_deposit = 10u128.pow(22);
+        testing_env!(context.clone());
+        let mut contract = Contract::new(spensa());
+        let mut public_key = vec![0];
+        public_key.extend_from_slice(attestation_keypair().public.as_bytes());
+        contract.set_attestation_signer(Some(PublicKey::try_from(public_key).unwrap()));
+
+        // a newer attestation extends the claim
+        let (older, older_signature) = signed_attestation(rainbow(), 650, NANOS_PER_DAY);
+        contract.submit_threshold_attestation(older, older_signature);
+        let (newer, newer_signature) = signed_attestation(rainbow(), 650, 2 * NANOS_PER_DAY);
+        contract.submit_threshold_attestation(newer, newer_signature);
+        assert_eq!(U64(32 * NANOS_PER_DAY), contract.claims(rainbow())[0].expires_at);
+
+        // replaying the older one can't shorten it
+        context.storage_usage = env::storage_usage();
+        testing_env!(context);
+        let (older, older_signature) = signed_attestation(rainbow(), 650, NANOS_PER_DAY);
+        contract.submit_threshold_attestation(older, older_signature);
+    }
+
+    // let the owner whitelist a user, then let the user store the given score
+    fn store_score_as(contract: &mut Contract, context: &mut VMContext, account_id: AccountId, score: u16) {
+        context.signer_account_id = spensa();
+        context.predecessor_account_id = spensa();
+        context.attached_deposit = 10u128.pow(23);
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        contract.add_to_whitelist(&account_id, WhitelistGrant {
+            payload_hash: None,
+            uses: 1,
+            expires_at: None,
+            reason: "Monthly score".to_string(),
+        });
+
+        context.signer_account_id = account_id.clone();
+        context.predecessor_account_id = account_id;
+        context.attached_deposit = 0;
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        assert!(contract.store_score(score, format!("Score of {}", score)).successful_operation);
+    }
+
+    #[test]
+    fn score_disputes() {
+        let mut context = get_context(false, spensa());
+        testing_env!(context.clone());
+        let mut contract = Contract::new(spensa());
+        store_score_as(&mut contract, &mut context, rainbow(), 300);
+        context.block_timestamp = 31 * NANOS_PER_DAY;
+        store_score_as(&mut contract, &mut context, rainbow(), 310);
+
+        // rainbow disputes both its scores
+        let dispute = contract.file_dispute(0, "My loan was repaid on time".to_string());
+        assert_eq!(DisputeStatus::Open, dispute.status);
+        contract.file_dispute(1, "Same error as last month".to_string());
+        assert_eq!(DisputeStatus::Open, contract.dispute_status(rainbow(), 1).unwrap().status);
+        assert!(contract.dispute_status(rainbow(), 2).is_none());
+
+        // the owner rejects the first dispute and upholds the second one
+        context.predecessor_account_id = spensa();
+        testing_env!(context.clone());
+        contract.reject_dispute(rainbow(), 0, "The score is right".to_string());
+        let dispute = contract.uphold_dispute(rainbow(), 1, 640, "Score of 640".to_string(), "Repayment was missed".to_string());
+        assert_eq!(DisputeStatus::Upheld, dispute.status);
+        assert_eq!(Some(spensa()), dispute.resolver_id);
+        assert_eq!(DisputeStatus::Rejected, contract.dispute_status(rainbow(), 0).unwrap().status);
+
+        // the entry is amended, and the original one is kept in the audit log
+        let history = contract.query_score_history(rainbow().to_string());
+        assert_eq!(300, history.scores[0].score);
+        assert_eq!(640, history.scores[1].score);
+        assert_eq!(31 * NANOS_PER_DAY, history.scores[1].timestamp);
+        let audit_log = contract.score_audit_log(None, None);
+        assert_eq!(1, audit_log.len());
+        assert_eq!(310, audit_log[0].original.score);
+        assert_eq!(640, audit_log[0].corrected.score);
+    }
+
+    #[test]
+    #[should_panic(expected = "Dispute error: this dispute was already resolved")]
+    fn score_dispute_resolved_twice() {
+        let mut context = get_context(false, spensa());
+        testing_env!(context.clone());
+        let mut contract = Contract::new(spensa());
+        store_score_as(&mut contract, &mut context, rainbow(), 300);
+        contract.file_dispute(0, "My loan was repaid on time".to_string());
+
+        context.predecessor_account_id = spensa();
+        testing_env!(context);
+        contract.reject_dispute(rainbow(), 0, "The score is right".to_string());
+        contract.uphold_dispute(rainbow(), 0, 640, "Score of 640".to_string(), String::new());
+    }
+
+    #[test]
+    fn score_statistics() {
+        let mut context = get_context(false, spensa());
+        testing_env!(context.clone());
+        let mut contract = Contract::new(spensa());
+        assert_eq!(None, contract.score_statistics().mean);
+
+        store_score_as(&mut contract, &mut context, rainbow(), 550);
+        store_score_as(&mut contract, &mut context, doomslug(), 700);
+        context.block_timestamp = 31 * NANOS_PER_DAY;
+        // only the latest score of rainbow counts
+        store_score_as(&mut contract, &mut context, rainbow(), 810);
+
+        let stats = contract.score_statistics();
+        assert_eq!(2, stats.count);
+        assert_eq!(U64(1_510), stats.sum);
+        assert_eq!(Some(755), stats.mean);
+        assert_eq!((Some(700), Some(810)), (stats.min, stats.max));
+        let counts: Vec<u64> = stats.histogram.iter().map(|band| band.count).collect();
+        assert_eq!(vec![0, 0, 0, 1, 0, 1], counts);
+        assert_eq!(Some(500), stats.histogram[0].band.max);
+        assert_eq!("Excellent", stats.histogram[5].band.label);
+        assert_eq!(None, stats.histogram[5].band.max);
+
+        // a correction of a latest score updates the statistics
+        contract.file_dispute(1, "Wrong score".to_string());
+        context.predecessor_account_id = spensa();
+        testing_env!(context.clone());
+        contract.uphold_dispute(rainbow(), 1, 650, "Score of 650".to_string(), String::new());
+        let stats = contract.score_statistics();
+        assert_eq!((Some(650), Some(700)), (stats.min, stats.max));
+
+        // the histogram is rebuilt with the new bands
+        contract.set_score_bands(vec![
+            BandEntry { min: 0, label: "Low".to_string(), tier: 1 },
+            BandEntry { min: 675, label: "High".to_string(), tier: 2 },
+        ]);
+        let counts: Vec<u64> = contract.score_statistics().histogram.iter().map(|band| band.count).collect();
+        assert_eq!(vec![1, 1], counts);
+    }
+
+    #[test]
+    fn backfill_score_stats() {
+        let mut context = get_context(false, spensa());
+        testing_env!(context.clone());
+        let mut contract = Contract::new(spensa());
+        store_score_as(&mut contract, &mut context, rainbow(), 550);
+        store_score_as(&mut contract, &mut context, doomslug(), 700);
+
+        // forget the statistics, as if the scores were stored before they existed
+        contract.score_stats = ScoreStats::new(contract.score_bands.len());
+        contract.latest_score_counts.clear();
+        contract.score_stats_accounts.remove(&rainbow());
+        contract.score_stats_accounts.remove(&doomslug());
+
+        // replacing a score not counted yet only adds the new one
+        context.block_timestamp = 31 * NANOS_PER_DAY;
+        store_score_as(&mut contract, &mut context, rainbow(), 810);
+        assert_eq!((1, U64(810)), (contract.score_statistics().count, contract.score_statistics().sum));
+
+        // the backfill adds the users not counted yet, once
+        context.predecessor_account_id = spensa();
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        assert_eq!(1, contract.backfill_score_stats(vec![rainbow(), doomslug(), spensa()]));
+        assert_eq!(0, contract.backfill_score_stats(vec![doomslug()]));
+        let stats = contract.score_statistics();
+        assert_eq!((2, U64(1_510)), (stats.count, stats.sum));
+        assert_eq!((Some(700), Some(810)), (stats.min, stats.max));
+    }
+
+    #[test]
+    fn score_summary() {
+        let mut context = get_context(false, spensa());
+        testing_env!(context.clone());
+        let mut contract = Contract::new(spensa());
+        assert!(contract.score_summary(rainbow().to_string()).is_none());
+
+        context.block_timestamp = NANOS_PER_DAY;
+        store_score_as(&mut contract, &mut context, rainbow(), 640);
+        let summary = contract.score_summary(rainbow().to_string()).unwrap();
+        assert_eq!((640, None, None), (summary.latest, summary.previous, summary.delta));
+        assert_eq!(Some(31 * NANOS_PER_DAY + 1), summary.next_score_at);
+
+        context.block_timestamp = 40 * NANOS_PER_DAY;
+        store_score_as(&mut contract, &mut context, rainbow(), 600);
+        let summary = contract.score_summary(rainbow().to_string()).unwrap();
+        assert_eq!((600, Some(640), Some(-40)), (summary.latest, summary.previous, summary.delta));
+        assert_eq!(ScoreBand { min: 580, max: Some(670), label: "Fair".to_string(), tier: 3 }, summary.band);
+        assert_eq!(2, summary.count);
+        assert_eq!((NANOS_PER_DAY, 40 * NANOS_PER_DAY), (summary.first_timestamp, summary.last_timestamp));
+        assert_eq!(Some(70 * NANOS_PER_DAY + 1), summary.next_score_at);
+    }
+
+    #[test]
+    fn score_bands() {
+        let mut context = get_context(false, spensa());
+        testing_env!(context.clone());
+        let mut contract = Contract::new(spensa());
+        assert_eq!(6, contract.score_bands().len());
+        let band = contract.classify_score(812);
+        assert_eq!(("Excellent".to_string(), 6, None), (band.label, band.tier, band.max));
+        store_score_as(&mut contract, &mut context, rainbow(), 590);
+
+        // the owner replaces the table: stored scores keep the band they were written with
+        context.predecessor_account_id = spensa();
+        testing_env!(context.clone());
+        contract.set_score_bands(vec![
+            BandEntry { min: 0, label: "Subprime".to_string(), tier: 1 },
+            BandEntry { min: 620, label: "Prime".to_string(), tier: 2 },
+        ]);
+        assert_eq!("Subprime", contract.classify_score(590).label);
+        assert_eq!("Fair", contract.query_score_history(rainbow().to_string()).scores[0].band.label);
+        assert_eq!("Fair", contract.score_summary(rainbow().to_string()).unwrap().band.label);
+        assert_eq!("Fair", contract.get_score(rainbow(), ScoreType::Credit, None).unwrap().band.label);
+        assert_eq!(1, contract.score_statistics().histogram[0].count);
+    }
+
+    #[test]
+    #[should_panic(expected = "The first score band must start from 0")]
+    fn score_bands_not_from_zero() {
+        let context = get_context(false, spensa());
+        testing_env!(context);
+        let mut contract = Contract::new(spensa());
+        contract.set_score_bands(vec![BandEntry { min: 300, label: "Poor".to_string(), tier: 1 }]);
+    }
+
+    #[test]
+    fn subscriptions() {
+        let mut context = get_context(false, rainbow());
+        context.attached_deposit = 10u128.pow(22);
+        testing_env!(context.clone());
+        let mut contract = Contract::new(spensa());
+
+        // rainbow subscribes doomslug to its own scores, then raises its gas budget
+        assert!(contract.subscribe(doomslug(), Some(rainbow()), None));
+        assert!(!contract.subscribe(doomslug(), Some(rainbow()), Some(U64(10_000_000_000_000))));
+        assert_eq!(1, contract.subscribers(Some(rainbow()), None, None).len());
+        assert_eq!(rainbow(), contract.subscription(doomslug(), Some(rainbow())).unwrap().payer_id);
+        assert_eq!(
+            U64(10_000_000_000_000),
+            contract.subscription(doomslug(), Some(rainbow())).unwrap().gas
+        );
+
+        // rainbow removes the subscription
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        assert!(contract.unsubscribe(doomslug(), Some(rainbow())));
+        assert!(contract.subscribers(Some(rainbow()), None, None).is_empty());
+
+        // the owner subscribes doomslug to spensa's scores
+        context.predecessor_account_id = spensa();
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        assert!(contract.subscribe(doomslug(), Some(spensa()), None));
+        assert!(contract.subscribers(None, None, None).is_empty());
+
+        // storing a score notifies the subscriber
+        context.signer_account_id = spensa();
+        context.attached_deposit = 0;
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        assert!(contract.store_score(650, "Score of 650".to_string()).successful_operation);
+        assert!(near_sdk::test_utils::get_created_receipts()
+            .iter()
+            .any(|receipt| receipt.receiver_id.to_string() == doomslug().to_string()));
+
+        // the subscriber is dropped after failing 3 notifications in a row
+        for _ in 0..3 {
+            context.storage_usage = env::storage_usage();
+            testing_env!(
+                context.clone(),
+                near_sdk::VMConfig::test(),
+                near_sdk::RuntimeFeesConfig::test(),
+                Default::default(),
+                vec![near_sdk::PromiseResult::Failed]
+            );
+            contract.resolve_score_notification(doomslug(), Some(spensa()));
+        }
+        assert!(contract.subscription(doomslug(), Some(spensa())).is_none());
+        assert!(!contract.unsubscribe(doomslug(), Some(spensa())));
+    }
+
+    #[test]
+    #[should_panic(expected = "Subscription error: only the account or the owner can subscribe a contract to its scores")]
+    fn subscribe_to_another_account() {
+        let mut context = get_context(false, rainbow());
+        context.attached_deposit = 10u128.pow(22);
+        testing_env!(context);
+        let mut contract = Contract::new(spensa());
+        contract.subscribe(rainbow(), Some(doomslug()), None);
+    }
+
+    #[test]
+    fn notification_gas_cap() {
+        let mut context = get_context(false, spensa());
+        context.attached_deposit = 10u128.pow(22);
+        testing_env!(context.clone());
+        let mut contract = Contract::new(spensa());
+
+        // 6 subscribers with the max gas budget, of which only 4 fit in the fan-out cap
+        let subscribers: Vec<AccountId> = (0..6)
+            .map(|i| format!("subscriber{}.testnet", i).try_into().unwrap())
+            .collect();
+        for subscriber_id in subscribers.iter() {
+            context.storage_usage = env::storage_usage();
+            testing_env!(context.clone());
+            contract.subscribe(subscriber_id.clone(), Some(spensa()), Some(U64(20_000_000_000_000)));
+        }
+
+        // the subscribers notified by a stored score
+        let notified = || -> Vec<String> {
+            near_sdk::test_utils::get_created_receipts()
+                .iter()
+                .map(|receipt| receipt.receiver_id.to_string())
+                .filter(|receiver_id| subscribers.iter().any(|id| &id.to_string() == receiver_id))
+                .collect()
+        };
+        context.signer_account_id = spensa();
+        context.attached_deposit = 0;
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        assert!(contract.store_score(650, "Score of 650".to_string()).successful_operation);
+        let first = notified();
+        assert_eq!(4, first.len());
+        assert!(near_sdk::test_utils::get_logs().iter().any(|log| log.contains("Notification gas cap reached")));
+
+        // the next score starts the fan-out from another subscriber
+        context.block_timestamp = 31 * NANOS_PER_DAY;
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        assert!(contract.store_score(680, "Score of 680".to_string()).successful_operation);
+        let second = notified();
+        assert_eq!(4, second.len());
+        assert_ne!(first, second);
+        assert!(second.iter().any(|subscriber_id| !first.contains(subscriber_id)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Rate limit exceeded: at most 1 queries per 3600 seconds")]
+    fn oracle_rate_limit() {
+        let mut context = get_context(false, spensa());
+        testing_env!(context.clone());
+        let mut contract = Contract::new(spensa());
+        contract.set_oracle_config(OracleConfig {
+            query_fee: U128(1_000),
+            max_queries_per_window: Some(1),
+            ..OracleConfig::default()
+        });
+        assert_eq!(U128(1_000), contract.oracle_config().query_fee);
+
+        // the caller pays the fee and the storage of its query counter
+        context.predecessor_account_id = doomslug();
+        context.attached_deposit = 10u128.pow(22);
+        testing_env!(context.clone());
+        assert!(contract.get_score_paid(rainbow(), ScoreType::Credit, None).is_none());
+
+        // the free method isn't metered for callers querying in their own transaction
+        context.signer_account_id = doomslug();
+        context.storage_usage = env::storage_usage();
+        context.attached_deposit = 0;
+        testing_env!(context.clone());
+        assert!(contract.get_score(rainbow(), ScoreType::Credit, None).is_none());
+
+        // a second paid query in the same window is rejected
+        context.attached_deposit = 10u128.pow(22);
+        testing_env!(context);
+        contract.get_score_paid(rainbow(), ScoreType::Credit, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Oracle error: contracts must query scores through get_score_paid() while a query fee or rate limit is set")]
+    fn oracle_free_query_cross_contract() {
+        let mut context = get_context(false, spensa());
+        testing_env!(context.clone());
+        let mut contract = Contract::new(spensa());
+        contract.set_oracle_config(OracleConfig { query_fee: U128(1_000), ..OracleConfig::default() });
+
+        // a contract called by rainbow can't read the score for free
+        context.signer_account_id = rainbow();
+        context.predecessor_account_id = doomslug();
+        testing_env!(context);
+        contract.get_score(rainbow(), ScoreType::Credit, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Quota exceeded: spensa.testnet can store at most 1 scores per hour")]
+    fn submission_quotas() {
+        let mut context = get_context(false, spensa());
+        context.signer_account_id = spensa();
+        context.block_timestamp = NANOS_PER_DAY;
+        testing_env!(context.clone());
+        let mut contract = Contract::new(spensa());
+        contract.set_default_submission_quota(SubmissionQuota { per_hour: Some(5), per_day: None });
+        contract.set_submission_quota(spensa(), Some(SubmissionQuota { per_hour: Some(1), per_day: Some(10) }));
+        assert_eq!(Some(5), contract.submission_quota(rainbow()).per_hour);
+
+        // the owner's score uses up its hourly quota
+        contract.store_score(720, "Score of 720".to_string());
+        let remaining = contract.remaining_quota(spensa());
+        assert_eq!(Some(0), remaining.this_hour);
+        assert_eq!(Some(U64(NANOS_PER_DAY + 3_600 * NANOS_PER_SEC)), remaining.hour_resets_at);
+        assert_eq!(Some(9), remaining.today);
+        assert_eq!(None, remaining.global_this_hour);
+
+        // the next hour, the quota is available again
+        context.block_timestamp = NANOS_PER_DAY + 3_600 * NANOS_PER_SEC;
+        testing_env!(context.clone());
+        assert_eq!(Some(1), contract.remaining_quota(spensa()).this_hour);
+        assert_eq!(None, contract.remaining_quota(spensa()).hour_resets_at);
+
+        // a commitment counts against the same quota
+        context.block_timestamp = NANOS_PER_DAY + 1_800 * NANOS_PER_SEC;
+        testing_env!(context);
+        let commitment = score_commitment_hash(&spensa(), 720, &[7; 32]);
+        contract.store_score_commitment(Base64VecU8(commitment));
+    }
+
+    #[test]
+    #[should_panic(expected = "Throughput limit exceeded: the contract accepts at most 1 scores per hour from all oracles")]
+    fn global_submission_limit() {
+        let mut context = get_context(false, spensa());
+        testing_env!(context.clone());
+        let mut contract = Contract::new(spensa());
+        contract.set_global_submission_limit(Some(1));
+        assert_eq!(Some(1), contract.global_submission_limit());
+
+        store_score_as(&mut contract, &mut context, rainbow(), 650);
+        assert_eq!(Some(0), contract.remaining_quota(doomslug()).global_this_hour);
+        assert_eq!(None, contract.remaining_quota(doomslug()).this_hour);
+        store_score_as(&mut contract, &mut context, doomslug(), 700);
+    }
+
+    // write the state of the original version of the contract
+    fn write_legacy_state() {
+        let mut whitelist = near_sdk::collections::LookupSet::new(StorageKey::WhiteList.try_to_vec().unwrap());
+        whitelist.insert(&rainbow());
+        // a score of spensa, in the original layout of the score records
+        let mut records = LookupMap::new(b"m");
+        let mut history = Vector::new(StorageKey::Accounts { account_hash: env::sha256(spensa().as_bytes()) });
+        history.push(&User { score: 610, timestamp: 0, description: b"Score of 610".to_vec() });
+        records.insert(&spensa().to_string(), &history);
+        env::state_write(&OldContract {
+            owner_id: spensa(),
+            records,
+            contract_state: State { user_count: 3, score_count: 5 },
+            whitelist,
+        });
+    }
+
+    #[test]
+    fn migrate_state() {
+        let mut context = get_context(false, spensa());
+        context.signer_account_id = spensa();
+        context.block_timestamp = 31 * NANOS_PER_DAY;
+        testing_env!(context);
+        write_legacy_state();
+        let mut contract = Contract::migrate();
+
+        // the owner and the counters are kept, the new fields get their defaults
+        let state = contract.read_state();
+        assert_eq!(("spensa.testnet".to_string(), 3, 5), (state.owner, state.user_count, state.score_count));
+        assert!(contract.whitelist.is_empty());
+        assert_eq!(6, contract.score_bands().len());
+
+        // the scores stored before the migration are read back, with their band
+        let history = contract.query_score_history(spensa().to_string());
+        assert_eq!((610, "Fair"), (history.scores[0].score, history.scores[0].band.label.as_str()));
+
+        // the migrated contract stores scores again
+        assert!(contract.store_score(700, "Score of 700".to_string()).successful_operation);
+        assert_eq!(6, contract.read_state().score_count);
+        assert_eq!(1, contract.score_statistics().count);
+    }
+}
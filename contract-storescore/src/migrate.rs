@@ -0,0 +1,35 @@
+use near_sdk::collections::LookupSet;
+use near_sdk::{env, near_bindgen, AccountId};
+use crate::*;
+
+/*
+ * State migration.
+ * The state of a contract deployed with the original version of this code only holds the fields
+ * of OldContract below: redeploying the current code on top of it requires calling migrate()
+ * in the same transaction, else every call fails to deserialize the state.
+ * migrate() keeps the score records and the user and score counters, and initializes every field
+ * added since then the way new() does.
+ * The original whitelist was a LookupSet, which can't be listed: the accounts it held are not
+ * carried over, and the owner grants them again with add_to_whitelist().
+ */
+
+// the contract state as laid out by the original version of this code
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct OldContract {
+    pub owner_id: AccountId,
+    pub records: LookupMap<String, Vector<User>>,
+    pub contract_state: State,
+    pub whitelist: LookupSet<AccountId>,
+}
+
+#[near_bindgen]
+impl Contract {
+    // migrate the state written by the original version of this code to the current layout.
+    // This method can be called only by the contract account itself, e.g., in the deploy transaction.
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let old: OldContract = env::state_read().expect("Migration error: no state to migrate");
+        Self::with_state(old.owner_id, old.records, old.contract_state)
+    }
+}
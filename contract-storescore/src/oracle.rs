@@ -0,0 +1,174 @@
+use near_sdk::{env, ext_contract, near_bindgen, AccountId, Balance, Promise};
+use near_sdk::json_types::{U128, U64};
+use crate::*;
+
+/*
+ * Score oracle interface.
+ * Other contracts (lending pools, DAOs) read scores through the ScoreOracle trait below, which returns
+ * the latest score of the given type of an account, or None if the account has no such score or if
+ * its latest score is older than the max age the caller accepts. It comes in two flavours:
+ * - get_score() is free, and can be called off chain as well
+ * - get_score_paid() is the metered entry point: the owner can optionally charge a fee per query and
+ *   rate limit each caller to a number of queries per time window. Rate-limited callers pay for the
+ *   storage of their query counter.
+ * Once a fee or a rate limit is set, contracts must go through get_score_paid(): get_score() rejects
+ * cross-contract calls, and can then only be called in a transaction signed by the caller itself.
+ */
+
+// kind of score served by the oracle
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum ScoreType {
+    // the credit score stored with store_score(), the only kind of score recorded for now
+    Credit,
+}
+
+// interface of the score oracle, to be imported by the contracts querying it
+#[ext_contract(ext_score_oracle)]
+pub trait ScoreOracle {
+    // the latest score of the given type of the account,
+    // or None if it has none or if it's older than max_age nanoseconds.
+    // Rejects cross-contract calls when a query fee or a rate limit is set
+    fn get_score(&self, account_id: AccountId, score_type: ScoreType, max_age: Option<U64>) -> Option<Score>;
+
+    // same as get_score(), charging the query fee and counting the query against the rate limit
+    fn get_score_paid(&mut self, account_id: AccountId, score_type: ScoreType, max_age: Option<U64>) -> Option<Score>;
+}
+
+// optional fee and rate limit applied to get_score_paid()
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OracleConfig {
+    // fee (in yoctoNEAR) to attach to every query, 0 makes queries free
+    pub query_fee: U128,
+    // max number of queries a caller can make per window, None disables rate limiting
+    pub max_queries_per_window: Option<u32>,
+    // length of the rate limiting window, in nanoseconds
+    pub window: U64,
+}
+
+impl Default for OracleConfig {
+    fn default() -> Self {
+        Self {
+            query_fee: U128(0),
+            max_queries_per_window: None,
+            window: U64(3_600 * NANOS_PER_SEC),
+        }
+    }
+}
+
+// number of calls counted in a fixed time window, e.g. the queries of a caller
+#[derive(BorshDeserialize, BorshSerialize, Clone)]
+pub struct CounterWindow {
+    // when the window started, Unix epoch in nanoseconds
+    pub started_at: u64,
+    pub count: u32,
+}
+
+impl CounterWindow {
+    // the window in progress at the given time: this one, or a new empty one if it's over
+    pub(crate) fn at(window: Option<CounterWindow>, now: u64, length: u64) -> CounterWindow {
+        window
+            .filter(|window| now - window.started_at < length)
+            .unwrap_or(CounterWindow { started_at: now, count: 0 })
+    }
+}
+
+#[near_bindgen]
+impl ScoreOracle for Contract {
+    // return the latest score of the given type of the account if it's at most max_age nanoseconds old (if given)
+    fn get_score(&self, account_id: AccountId, score_type: ScoreType, max_age: Option<U64>) -> Option<Score> {
+        // METERING CHECK
+        // contracts can't skip the fee and the rate limit of get_score_paid()
+        if self.oracle_config.query_fee.0 > 0 || self.oracle_config.max_queries_per_window.is_some() {
+            assert_eq!(
+                env::predecessor_account_id(),
+                env::signer_account_id(),
+                "Oracle error: contracts must query scores through get_score_paid() while a query fee or rate limit is set"
+            );
+        }
+
+        self.latest_score(account_id, score_type, max_age)
+    }
+
+    // same as get_score(). The caller must attach the query fee and, when rate limiting is on,
+    // enough NEAR to cover the storage of its query counter. The surplus is refunded.
+    #[payable]
+    fn get_score_paid(&mut self, account_id: AccountId, score_type: ScoreType, max_age: Option<U64>) -> Option<Score> {
+        let initial_storage_usage = env::storage_usage();
+
+        // RATE LIMIT
+        if let Some(max_queries) = self.oracle_config.max_queries_per_window {
+            let caller_id = env::predecessor_account_id();
+            let now = env::block_timestamp();
+            let mut window = CounterWindow::at(self.oracle_usage.get(&caller_id), now, self.oracle_config.window.0);
+            assert!(
+                window.count < max_queries,
+                "Rate limit exceeded: at most {} queries per {} seconds",
+                max_queries,
+                self.oracle_config.window.0 / NANOS_PER_SEC
+            );
+            window.count += 1;
+            self.oracle_usage.insert(&caller_id, &window);
+        }
+
+        // FEE
+        let storage_used = env::storage_usage().saturating_sub(initial_storage_usage);
+        charge_query(self.oracle_config.query_fee.0, storage_used);
+
+        self.latest_score(account_id, score_type, max_age)
+    }
+}
+
+impl Contract {
+    // the latest score of the given type of the account, unless it's older than max_age nanoseconds
+    fn latest_score(&self, account_id: AccountId, score_type: ScoreType, max_age: Option<U64>) -> Option<Score> {
+        // credit scores are the only scores recorded
+        let ScoreType::Credit = score_type;
+
+        // the latest score is the last one of the history
+        let history = self.records.get(&account_id.to_string())?;
+        let index = history.len().checked_sub(1)?;
+        let latest = history.get(index)?;
+        if let Some(max_age) = max_age {
+            if env::block_timestamp().saturating_sub(latest.timestamp) > max_age.0 {
+                return None;
+            }
+        }
+        Some(self.to_score(&account_id, index, &latest))
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    // query the fee and rate limit applied to get_score_paid()
+    pub fn oracle_config(&self) -> OracleConfig {
+        self.oracle_config.clone()
+    }
+
+    // set the fee and rate limit applied to get_score_paid().
+    // This method can be called only by the smart contract owner.
+    pub fn set_oracle_config(&mut self, config: OracleConfig) {
+        // ensure the function was called by the smart contract owner, else panic
+        self.assert_called_by_owner();
+
+        assert!(config.window.0 > 0, "The rate limiting window must be greater than zero");
+        self.oracle_config = config;
+    }
+}
+
+// charge the query fee and the storage used up by the query to the attached deposit, and refund the surplus
+fn charge_query(query_fee: Balance, storage_used: u64) {
+    let required_cost = query_fee + env::storage_byte_cost() * Balance::from(storage_used);
+    let attached_deposit = env::attached_deposit();
+    assert!(
+        required_cost <= attached_deposit,
+        "Must attach {} yoctoNEAR to cover the query fee and storage",
+        required_cost,
+    );
+    let refund = attached_deposit - required_cost;
+    if refund > 1 {
+        Promise::new(env::predecessor_account_id()).transfer(refund);
+    }
+}
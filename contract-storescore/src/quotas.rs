@@ -0,0 +1,168 @@
+use near_sdk::{env, near_bindgen, AccountId};
+use near_sdk::json_types::U64;
+use crate::*;
+
+/*
+ * Submission quotas.
+ * Every account writing scores or score commitments (the owner and the whitelisted accounts, called
+ * oracles here) is limited to a number of submissions per hour and per day, so that a leaked oracle key
+ * can't flood the contract. The owner sets a default quota, overrides it per oracle, and caps the
+ * submissions of all oracles together per hour. Each limit is counted in a fixed window that starts
+ * with the first submission after the previous window ended. No limit is set at initialization.
+ */
+
+const HOUR: u64 = 3_600 * NANOS_PER_SEC;
+
+// max number of submissions of an oracle, per hour and per day: None means unlimited
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SubmissionQuota {
+    pub per_hour: Option<u32>,
+    pub per_day: Option<u32>,
+}
+
+// submissions made by an oracle in its current hour and day windows
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct SubmissionCounters {
+    pub hour: CounterWindow,
+    pub day: CounterWindow,
+}
+
+// submissions an oracle can still make, as returned by the remaining_quota() view.
+// None means unlimited; the reset times are Unix epochs in nanoseconds, None if the window isn't running
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RemainingQuota {
+    pub this_hour: Option<u32>,
+    pub hour_resets_at: Option<U64>,
+    pub today: Option<u32>,
+    pub day_resets_at: Option<U64>,
+    // submissions all oracles together can still make in the current global hour
+    pub global_this_hour: Option<u32>,
+    pub global_resets_at: Option<U64>,
+}
+
+// remaining submissions and end of a running window, given its limit
+fn remaining(window: &CounterWindow, limit: Option<u32>, now: u64, length: u64) -> (Option<u32>, Option<U64>) {
+    let running = window.count > 0 && now - window.started_at < length;
+    let used = if running { window.count } else { 0 };
+    (
+        limit.map(|limit| limit.saturating_sub(used)),
+        if running { Some(U64(window.started_at + length)) } else { None },
+    )
+}
+
+#[near_bindgen]
+impl Contract {
+    // query the quota applied to an oracle: its own quota if the owner set one, else the default quota
+    pub fn submission_quota(&self, oracle_id: AccountId) -> SubmissionQuota {
+        self.submission_quotas
+            .get(&oracle_id)
+            .unwrap_or_else(|| self.default_submission_quota.clone())
+    }
+
+    // query the max number of submissions of all oracles together per hour, None means unlimited
+    pub fn global_submission_limit(&self) -> Option<u32> {
+        self.global_submission_limit
+    }
+
+    // query the submissions an oracle can still make in its current windows
+    pub fn remaining_quota(&self, oracle_id: AccountId) -> RemainingQuota {
+        let now = env::block_timestamp();
+        let quota = self.submission_quota(oracle_id.clone());
+        let idle = CounterWindow { started_at: now, count: 0 };
+        let (hour, day) = self
+            .submission_counters
+            .get(&oracle_id)
+            .map_or((idle.clone(), idle.clone()), |counters| (counters.hour, counters.day));
+        let (this_hour, hour_resets_at) = remaining(&hour, quota.per_hour, now, HOUR);
+        let (today, day_resets_at) = remaining(&day, quota.per_day, now, NANOS_PER_DAY);
+        let (global_this_hour, global_resets_at) =
+            remaining(&self.global_submissions, self.global_submission_limit, now, HOUR);
+        RemainingQuota {
+            this_hour,
+            hour_resets_at,
+            today,
+            day_resets_at,
+            global_this_hour,
+            global_resets_at,
+        }
+    }
+
+    // set the quota applied to the oracles without a quota of their own.
+    // This method can be called only by the smart contract owner.
+    pub fn set_default_submission_quota(&mut self, quota: SubmissionQuota) {
+        // ensure the function was called by the smart contract owner, else panic
+        self.assert_called_by_owner();
+
+        self.default_submission_quota = quota;
+    }
+
+    // set the quota of an oracle, or pass None to apply the default quota to it again.
+    // This method can be called only by the smart contract owner.
+    pub fn set_submission_quota(&mut self, oracle_id: AccountId, quota: Option<SubmissionQuota>) {
+        // ensure the function was called by the smart contract owner, else panic
+        self.assert_called_by_owner();
+
+        match quota {
+            Some(quota) => self.submission_quotas.insert(&oracle_id, &quota),
+            None => self.submission_quotas.remove(&oracle_id),
+        };
+    }
+
+    // set the max number of submissions of all oracles together per hour, None means unlimited.
+    // This method can be called only by the smart contract owner.
+    pub fn set_global_submission_limit(&mut self, limit: Option<u32>) {
+        // ensure the function was called by the smart contract owner, else panic
+        self.assert_called_by_owner();
+
+        self.global_submission_limit = limit;
+    }
+}
+
+impl Contract {
+    // count a submission of the oracle against its quota and the global limit, else panic
+    pub(crate) fn use_submission_quota(&mut self, oracle_id: &AccountId) {
+        let now = env::block_timestamp();
+
+        let mut global = CounterWindow::at(Some(self.global_submissions.clone()), now, HOUR);
+        if let Some(limit) = self.global_submission_limit {
+            assert!(
+                global.count < limit,
+                "Throughput limit exceeded: the contract accepts at most {} scores per hour from all oracles",
+                limit
+            );
+        }
+
+        let quota = self.submission_quota(oracle_id.clone());
+        let counters = self.submission_counters.get(oracle_id);
+        let (hour, day) = match counters {
+            Some(counters) => (Some(counters.hour), Some(counters.day)),
+            None => (None, None),
+        };
+        let mut hour = CounterWindow::at(hour, now, HOUR);
+        let mut day = CounterWindow::at(day, now, NANOS_PER_DAY);
+        if let Some(limit) = quota.per_hour {
+            assert!(
+                hour.count < limit,
+                "Quota exceeded: {} can store at most {} scores per hour",
+                oracle_id,
+                limit
+            );
+        }
+        if let Some(limit) = quota.per_day {
+            assert!(
+                day.count < limit,
+                "Quota exceeded: {} can store at most {} scores per day",
+                oracle_id,
+                limit
+            );
+        }
+
+        global.count += 1;
+        hour.count += 1;
+        day.count += 1;
+        self.global_submissions = global;
+        self.submission_counters.insert(oracle_id, &SubmissionCounters { hour, day });
+    }
+}
@@ -0,0 +1,156 @@
+use near_sdk::{env, near_bindgen, AccountId};
+use near_sdk::json_types::U64;
+use crate::*;
+
+/*
+ * Score distribution statistics.
+ * The contract keeps running statistics over the latest score of every user: their count, their sum,
+ * a histogram by score bands, and how many users hold each score value (in a TreeMap, which yields
+ * the min and max). Storing, correcting or deleting a latest score updates them in constant time
+ * (the TreeMap holds at most one entry per score value, whatever the number of users), so that
+ * score_statistics() is a cheap view. Only changing the band table rebuilds the histogram, out of the
+ * score values.
+ * The users counted are tracked, so that the latest scores stored before the statistics existed are
+ * added once, by the owner, with backfill_score_stats(); until then replacing one of them just adds the
+ * new score.
+ */
+
+// running statistics over the latest score of every user
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ScoreStats {
+    // number of users with a score, i.e. number of latest scores
+    pub count: u64,
+    // sum of the latest scores
+    pub sum: u64,
+    // number of latest scores in every band of the band table
+    pub histogram: Vec<u64>,
+}
+
+impl ScoreStats {
+    pub(crate) fn new(band_count: usize) -> Self {
+        Self {
+            count: 0,
+            sum: 0,
+            histogram: vec![0; band_count],
+        }
+    }
+}
+
+// number of latest scores in a band, as returned by the score_statistics() view
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BandCount {
+    pub band: ScoreBand,
+    pub count: u64,
+}
+
+// statistics over the latest score of every user, as returned by the score_statistics() view
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ScoreStatistics {
+    // number of users with a score
+    pub count: u64,
+    pub sum: U64,
+    // mean of the latest scores, rounded to the nearest integer
+    pub mean: Option<u16>,
+    pub min: Option<u16>,
+    pub max: Option<u16>,
+    pub histogram: Vec<BandCount>,
+}
+
+#[near_bindgen]
+impl Contract {
+    // gasless query of the statistics over the latest score of every user
+    pub fn score_statistics(&self) -> ScoreStatistics {
+        let stats = &self.score_stats;
+        let histogram = self
+            .score_bands
+            .iter()
+            .zip(stats.histogram.iter())
+            .map(|(entry, count)| BandCount {
+                band: self.classify_score(entry.min),
+                count: *count,
+            })
+            .collect();
+        ScoreStatistics {
+            count: stats.count,
+            sum: U64(stats.sum),
+            mean: (stats.sum + stats.count / 2).checked_div(stats.count).map(|mean| mean as u16),
+            min: self.latest_score_counts.min(),
+            max: self.latest_score_counts.max(),
+            histogram,
+        }
+    }
+
+    // add the latest scores of the given users to the statistics, skipping the users already counted
+    // and the users without scores. Returns the number of users added.
+    // The score records can't be listed: the owner passes the users in batches that fit in the gas limit.
+    // This method can be called only by the smart contract owner.
+    pub fn backfill_score_stats(&mut self, account_ids: Vec<AccountId>) -> u64 {
+        // ensure the function was called by the smart contract owner, else panic
+        self.assert_called_by_owner();
+
+        let mut added = 0;
+        for account_id in account_ids {
+            if self.score_stats_accounts.contains(&account_id) {
+                continue;
+            }
+            let latest = self.records.get(&account_id.to_string()).and_then(|history| {
+                history.len().checked_sub(1).and_then(|index| history.get(index))
+            });
+            if let Some(latest) = latest {
+                self.update_score_stats(&account_id, None, Some(latest.score));
+                added += 1;
+            }
+        }
+        added
+    }
+}
+
+// decrease a statistic, which can't go below zero as long as it counts the score being removed
+fn decrease(value: u64, amount: u64) -> u64 {
+    value
+        .checked_sub(amount)
+        .unwrap_or_else(|| env::panic_str("Statistics error: the score being replaced isn't counted in the statistics"))
+}
+
+impl Contract {
+    // update the statistics when the latest score of a user changes:
+    // old is None for a new user, new is None when the user's scores are deleted.
+    // The old score of a user not counted yet (stored before the statistics existed) is ignored
+    pub(crate) fn update_score_stats(&mut self, account_id: &AccountId, old: Option<u16>, new: Option<u16>) {
+        let old = old.filter(|_| self.score_stats_accounts.contains(account_id));
+        if let Some(score) = old {
+            let count = self.latest_score_counts.get(&score).unwrap_or(0);
+            if count <= 1 {
+                self.latest_score_counts.remove(&score);
+            } else {
+                self.latest_score_counts.insert(&score, &(count - 1));
+            }
+            let index = self.band_index(score);
+            self.score_stats.histogram[index] = decrease(self.score_stats.histogram[index], 1);
+            self.score_stats.sum = decrease(self.score_stats.sum, u64::from(score));
+            self.score_stats.count = decrease(self.score_stats.count, 1);
+        }
+        if let Some(score) = new {
+            let count = self.latest_score_counts.get(&score).unwrap_or(0);
+            self.latest_score_counts.insert(&score, &(count + 1));
+            let index = self.band_index(score);
+            self.score_stats.histogram[index] += 1;
+            self.score_stats.sum += u64::from(score);
+            self.score_stats.count += 1;
+            self.score_stats_accounts.insert(account_id);
+        } else {
+            self.score_stats_accounts.remove(account_id);
+        }
+    }
+
+    // rebuild the histogram out of the score values, after the band table changed
+    pub(crate) fn rebuild_score_histogram(&mut self) {
+        let mut histogram = vec![0; self.score_bands.len()];
+        for (score, count) in self.latest_score_counts.iter() {
+            histogram[self.band_index(score)] += count;
+        }
+        self.score_stats.histogram = histogram;
+    }
+}
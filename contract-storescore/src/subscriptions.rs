@@ -0,0 +1,254 @@
+use near_sdk::{env, log, near_bindgen, AccountId, Gas, Promise, PromiseResult};
+use near_sdk::collections::UnorderedMap;
+use near_sdk::json_types::U64;
+use near_sdk::serde_json::json;
+use crate::*;
+
+/*
+ * Score subscriptions.
+ * An account subscribes a contract (e.g. a lending pool) to its own scores, attaching enough NEAR to cover
+ * the storage of the subscription; only the owner can subscribe a contract to the scores of any account,
+ * or of all accounts. The subscriber, the account and the owner can all remove the subscription, and its
+ * storage is refunded to whoever paid for it.
+ * After each successful store_score() the contract calls on_score_updated(account_id, score, timestamp) on
+ * every subscriber, fire and forget: the score is stored whatever the subscribers do. Each notification
+ * gets the gas budget of its subscription, and the whole fan-out is capped at MAX_NOTIFICATION_FAN_OUT_GAS:
+ * subscribers beyond it are skipped, and logged. Every fan-out starts from a different subscriber, so that
+ * the ones left out change from one score to the next. A callback counts the notifications that failed in a row: after
+ * MAX_NOTIFICATION_FAILURES failures the subscription is removed.
+ */
+
+// gas budget of a notification, unless the subscriber chooses another one
+const DEFAULT_NOTIFICATION_GAS: Gas = Gas(5_000_000_000_000);
+// the gas budget a subscriber can choose is bounded, so that store_score() stays affordable
+const MIN_NOTIFICATION_GAS: Gas = Gas(2_000_000_000_000);
+const MAX_NOTIFICATION_GAS: Gas = Gas(20_000_000_000_000);
+const GAS_FOR_RESOLVE_NOTIFICATION: Gas = Gas(5_000_000_000_000);
+// total gas of the notifications (and their callbacks) scheduled by a single store_score()
+const MAX_NOTIFICATION_FAN_OUT_GAS: Gas = Gas(100_000_000_000_000);
+// gas left to store_score() to complete once the notifications are scheduled
+const GAS_RESERVED_FOR_STORE_SCORE: Gas = Gas(5_000_000_000_000);
+
+// max number of subscribers to an account, and to all accounts
+const MAX_SUBSCRIBERS: u64 = 10;
+// number of notifications in a row a subscriber can fail before being unsubscribed
+const MAX_NOTIFICATION_FAILURES: u8 = 3;
+
+// a contract subscribed to score updates
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Subscription {
+    // gas attached to each on_score_updated() call
+    pub gas: U64,
+    // number of notifications that failed in a row
+    pub failures: u8,
+    // account that paid for the storage of the subscription, refunded when it is removed
+    pub payer_id: AccountId,
+}
+
+// a subscriber and its subscription, as returned by the subscribers() view
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SubscriberEntry {
+    pub subscriber_id: AccountId,
+    pub subscription: Subscription,
+}
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    //Subscribes a contract to the scores of the given account, or of all accounts if None.
+    //Only the account itself can subscribe a contract to its scores; the owner can subscribe one to any account's.
+    //Returns `true` if it wasn't subscribed before, `false` otherwise (in which case its gas budget is updated).
+    //The caller must attach enough NEAR to cover the storage of the subscription.
+    pub fn subscribe(&mut self, subscriber_id: AccountId, account_id: Option<AccountId>, gas: Option<U64>) -> bool {
+        let payer_id = env::predecessor_account_id();
+        assert!(
+            payer_id == self.owner_id || account_id.as_ref() == Some(&payer_id),
+            "Subscription error: only the account or the owner can subscribe a contract to its scores"
+        );
+        let gas = gas.map_or(DEFAULT_NOTIFICATION_GAS, |gas| Gas(gas.0));
+        assert!(
+            gas >= MIN_NOTIFICATION_GAS && gas <= MAX_NOTIFICATION_GAS,
+            "The gas budget of a notification must be between {} and {}",
+            MIN_NOTIFICATION_GAS.0,
+            MAX_NOTIFICATION_GAS.0
+        );
+
+        let initial_storage_usage = env::storage_usage();
+        let mut subscribers = self.subscribers_of(&account_id);
+        let is_new = subscribers.get(&subscriber_id).is_none();
+        assert!(
+            !is_new || subscribers.len() < MAX_SUBSCRIBERS,
+            "Subscription error: there are already {} subscribers",
+            MAX_SUBSCRIBERS
+        );
+        // whoever paid for the subscription first keeps being refunded its storage
+        let payer_id = subscribers.get(&subscriber_id).map_or(payer_id, |subscription| subscription.payer_id);
+        subscribers.insert(&subscriber_id, &Subscription { gas: U64(gas.0), failures: 0, payer_id });
+        self.subscriptions.insert(&account_id, &subscribers);
+
+        // updating a subscription doesn't use up more storage
+        let storage_used = env::storage_usage().saturating_sub(initial_storage_usage);
+        charge_storage(storage_used);
+
+        is_new
+    }
+
+    //Unsubscribes a contract from the scores of the given account, or of all accounts if None,
+    //and refunds the storage of the subscription to the account that paid for it.
+    //Can be called by the subscriber, by the account itself and by the owner.
+    //Returns `true` if it was subscribed, `false` otherwise.
+    pub fn unsubscribe(&mut self, subscriber_id: AccountId, account_id: Option<AccountId>) -> bool {
+        let caller = env::predecessor_account_id();
+        assert!(
+            caller == subscriber_id || caller == self.owner_id || account_id.as_ref() == Some(&caller),
+            "Subscription error: only the subscriber, the account or the owner can remove a subscription"
+        );
+        self.remove_subscription(&subscriber_id, &account_id)
+    }
+
+    //Query the subscription of a contract to the scores of the given account, or of all accounts if None
+    pub fn subscription(&self, subscriber_id: AccountId, account_id: Option<AccountId>) -> Option<Subscription> {
+        self.subscriptions
+            .get(&account_id)
+            .and_then(|subscribers| subscribers.get(&subscriber_id))
+    }
+
+    //Query the subscribers to the scores of the given account, or of all accounts if None, using pagination
+    pub fn subscribers(
+        &self,
+        account_id: Option<AccountId>,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<SubscriberEntry> {
+        self.subscriptions.get(&account_id).map_or(vec![], |subscribers| {
+            subscribers
+                .iter()
+                .skip(from_index.unwrap_or(0) as usize)
+                .take(limit.unwrap_or(50) as usize)
+                .map(|(subscriber_id, subscription)| SubscriberEntry { subscriber_id, subscription })
+                .collect()
+        })
+    }
+
+    //resolves a notification sent to a subscriber: counts the notifications that failed in a row
+    //and removes the subscription once they reach MAX_NOTIFICATION_FAILURES
+    #[private]
+    pub fn resolve_score_notification(&mut self, subscriber_id: AccountId, account_id: Option<AccountId>) {
+        let mut subscribers = match self.subscriptions.get(&account_id) {
+            Some(subscribers) => subscribers,
+            None => return,
+        };
+        // the subscriber may have unsubscribed in the meantime
+        let mut subscription = match subscribers.get(&subscriber_id) {
+            Some(subscription) => subscription,
+            None => return,
+        };
+
+        if let PromiseResult::Successful(_) = env::promise_result(0) {
+            if subscription.failures > 0 {
+                subscription.failures = 0;
+                subscribers.insert(&subscriber_id, &subscription);
+            }
+            return;
+        }
+
+        subscription.failures += 1;
+        if subscription.failures >= MAX_NOTIFICATION_FAILURES {
+            log!("{} failed {} notifications in a row and was unsubscribed", subscriber_id, subscription.failures);
+            self.remove_subscription(&subscriber_id, &account_id);
+        } else {
+            subscribers.insert(&subscriber_id, &subscription);
+        }
+    }
+}
+
+impl Contract {
+    // the subscribers to the given account, or to all accounts if None
+    fn subscribers_of(&self, account_id: &Option<AccountId>) -> UnorderedMap<AccountId, Subscription> {
+        self.subscriptions.get(account_id).unwrap_or_else(|| {
+            UnorderedMap::new(StorageKey::Subscribers {
+                account_hash: env::sha256(&account_id.try_to_vec().unwrap()),
+            })
+        })
+    }
+
+    // remove a subscription and refund the storage it released to the account that paid for it
+    fn remove_subscription(&mut self, subscriber_id: &AccountId, account_id: &Option<AccountId>) -> bool {
+        let mut subscribers = match self.subscriptions.get(account_id) {
+            Some(subscribers) => subscribers,
+            None => return false,
+        };
+        let initial_storage_usage = env::storage_usage();
+        let payer_id = match subscribers.remove(subscriber_id) {
+            Some(subscription) => subscription.payer_id,
+            None => return false,
+        };
+        if subscribers.is_empty() {
+            self.subscriptions.remove(account_id);
+        } else {
+            self.subscriptions.insert(account_id, &subscribers);
+        }
+        refund_released_storage(&payer_id, initial_storage_usage.saturating_sub(env::storage_usage()));
+        true
+    }
+
+    // notify the subscribers to the account and the subscribers to all accounts of its new score,
+    // starting from a subscriber that rotates with the number of scores stored.
+    // The notifications are detached promises, so their outcome doesn't affect store_score().
+    // Subscribers that don't fit in MAX_NOTIFICATION_FAN_OUT_GAS, or in the gas left, are skipped.
+    pub(crate) fn notify_subscribers(&self, account_id: &AccountId, score: &User) {
+        let args = json!({
+            "account_id": account_id,
+            "score": score.score,
+            "timestamp": score.timestamp,
+        })
+        .to_string()
+        .into_bytes();
+
+        let mut subscribers: Vec<(Option<AccountId>, AccountId, Subscription)> = vec![];
+        for target in [Some(account_id.clone()), None] {
+            if let Some(target_subscribers) = self.subscriptions.get(&target) {
+                subscribers.extend(
+                    target_subscribers
+                        .iter()
+                        .map(|(subscriber_id, subscription)| (target.clone(), subscriber_id, subscription)),
+                );
+            }
+        }
+        if subscribers.is_empty() {
+            return;
+        }
+        // the subscribers left out by the gas cap aren't always the same ones
+        let start = self.contract_state.score_count % subscribers.len() as u64;
+        subscribers.rotate_left(start as usize);
+
+        let mut fan_out_gas = 0;
+        for (target, subscriber_id, subscription) in subscribers {
+            let gas = Gas(subscription.gas.0);
+            let notification_gas = gas.0 + GAS_FOR_RESOLVE_NOTIFICATION.0;
+            if fan_out_gas + notification_gas > MAX_NOTIFICATION_FAN_OUT_GAS.0 {
+                log!("Notification gas cap reached, {} is not notified", subscriber_id);
+                continue;
+            }
+            let gas_left = env::prepaid_gas().0.saturating_sub(env::used_gas().0);
+            if gas_left < notification_gas + GAS_RESERVED_FOR_STORE_SCORE.0 {
+                log!("Not enough gas left to notify {}", subscriber_id);
+                continue;
+            }
+            fan_out_gas += notification_gas;
+            let resolve_args = json!({ "subscriber_id": subscriber_id, "account_id": target })
+                .to_string()
+                .into_bytes();
+            Promise::new(subscriber_id)
+                .function_call("on_score_updated".to_string(), args.clone(), 0, gas)
+                .then(Promise::new(env::current_account_id()).function_call(
+                    "resolve_score_notification".to_string(),
+                    resolve_args,
+                    0,
+                    GAS_FOR_RESOLVE_NOTIFICATION,
+                ));
+        }
+    }
+}
@@ -1,11 +1,51 @@
-use near_sdk::{env, AccountId, near_bindgen};
+use near_sdk::{env, AccountId, Balance, near_bindgen, Promise};
+use near_sdk::json_types::{Base64VecU8, U128, U64};
 use crate::*;
 
+/*
+ * Whitelist tickets.
+ * Every whitelisted account holds a grant telling what it can store, how many times and until when.
+ * Grants are stored in an UnorderedMap so that they can be listed, and the owner pays for their storage
+ * when adding them. Grants that are used up are removed by the store_score() call that used them up,
+ * while expired grants are removed lazily, either when the owner replaces them or by prune_whitelist(),
+ * which anyone can call. Either way the storage they released is refunded to the contract owner.
+ */
+
+// a whitelist grant: what a whitelisted account is allowed to store, and how many times
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct WhitelistGrant {
+    // sha256 hash of the borsh-serialized (score, description) pair the grantee can store,
+    // None allows any score
+    pub payload_hash: Option<Base64VecU8>,
+    // how many scores the grantee can still store
+    pub uses: u32,
+    // when the grant expires, Unix epoch in nanoseconds, None means it never expires
+    pub expires_at: Option<U64>,
+    // why the account was whitelisted
+    #[serde(default)]
+    pub reason: String,
+}
+
+impl WhitelistGrant {
+    // whether the grant can still be used at the given timestamp
+    pub(crate) fn is_active(&self, timestamp: u64) -> bool {
+        self.uses > 0 && self.expires_at.map_or(true, |expires_at| timestamp < expires_at.0)
+    }
+}
+
+// a whitelisted account and its grant, as returned by the whitelist() view
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct WhitelistEntry {
+    pub account_id: AccountId,
+    pub grant: WhitelistGrant,
+}
 
 #[near_bindgen]
 impl Contract {
     //Internal method to verify the predecessor was the smart contract owner
-    fn assert_called_by_owner(&self) {
+    pub(crate) fn assert_called_by_owner(&self) {
         assert_eq!(
             &env::predecessor_account_id(),
             &self.owner_id,
@@ -13,21 +53,171 @@ impl Contract {
         );
     }
 
-    //Adds the given account ID to the whitelist.
-    //Returns `true` if the account id was not in the whitelist before, `false` otherwise.
+    #[payable]
+    //Adds the given account ID to the whitelist with the given grant.
+    //Returns `true` if the account id was not in the whitelist before, `false` otherwise
+    //(in which case its grant is replaced).
+    //The owner must attach enough NEAR to cover the storage of the grant.
     //This method can be called only by the smart contract owner.
-    pub fn add_to_whitelist(&mut self, account_id: &AccountId) -> bool {
+    pub fn add_to_whitelist(&mut self, account_id: &AccountId, grant: WhitelistGrant) -> bool {
 
         //ensure the function was called by the smart contract owner, else panic
         self.assert_called_by_owner();
 
-        self.whitelist.insert(&account_id)
+        assert!(grant.uses > 0, "A whitelist grant must allow at least one use");
+        if let Some(payload_hash) = &grant.payload_hash {
+            assert_eq!(payload_hash.0.len(), 32, "Hash error: payload_hash must be a sha256 hash");
+        }
+        if let Some(expires_at) = grant.expires_at {
+            assert!(
+                env::block_timestamp() < expires_at.0,
+                "A whitelist grant can't expire in the past"
+            );
+        }
+
+        // an expired grant doesn't count as being whitelisted
+        let was_whitelisted = self
+            .whitelist
+            .get(account_id)
+            .map_or(false, |grant| grant.is_active(env::block_timestamp()));
+
+        let initial_storage_usage = env::storage_usage();
+        self.whitelist.insert(account_id, &grant);
+        // replacing a grant with a shorter one may release storage
+        let storage_usage = env::storage_usage();
+        if storage_usage >= initial_storage_usage {
+            charge_storage(storage_usage - initial_storage_usage);
+        } else {
+            refund_released_storage(&self.owner_id, initial_storage_usage - storage_usage);
+        }
+
+        !was_whitelisted
     }
 
-    //Removes the given account ID from the whitelist.
+    //Removes the given account ID from the whitelist and refunds its storage to the owner.
     //Returns `true` if the account id was in the whitelist, `false` otherwise.
     //This method can be called only by the smart contract owner.
     pub fn remove_from_whitelist(&mut self, account_id: &AccountId) -> bool {
-        self.whitelist.remove(&account_id)
+        //ensure the function was called by the smart contract owner, else panic
+        self.assert_called_by_owner();
+
+        self.remove_grant(account_id)
+    }
+
+    //Removes up to 'limit' expired grants from the whitelist and refunds their storage to the owner.
+    //Returns how many grants were removed. Anyone can call this method.
+    pub fn prune_whitelist(&mut self, limit: Option<u64>) -> u64 {
+        let now = env::block_timestamp();
+        let expired: Vec<AccountId> = self
+            .whitelist
+            .iter()
+            .filter(|(_, grant)| !grant.is_active(now))
+            .take(limit.unwrap_or(50) as usize)
+            .map(|(account_id, _)| account_id)
+            .collect();
+
+        for account_id in expired.iter() {
+            self.remove_grant(account_id);
+        }
+        expired.len() as u64
+    }
+
+    //Query the whitelist grant of the given account ID
+    pub fn whitelist_grant(&self, account_id: AccountId) -> Option<WhitelistGrant> {
+        self.whitelist.get(&account_id)
+    }
+
+    //Query whether the given account ID holds a grant that can still be used
+    pub fn is_whitelisted(&self, account_id: AccountId) -> bool {
+        self.whitelist
+            .get(&account_id)
+            .map_or(false, |grant| grant.is_active(env::block_timestamp()))
+    }
+
+    //Query the whitelisted accounts whose grant can still be used, using pagination
+    pub fn whitelist(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<WhitelistEntry> {
+        let now = env::block_timestamp();
+        self.whitelist
+            .iter()
+            // leave out the expired grants, which are waiting to be pruned
+            .filter(|(_, grant)| grant.is_active(now))
+            .skip(u128::from(from_index.unwrap_or(U128(0))) as usize)
+            .take(limit.unwrap_or(50) as usize)
+            .map(|(account_id, grant)| WhitelistEntry { account_id, grant })
+            .collect()
+    }
+}
+
+impl Contract {
+    // check a score against the whitelist grant of the account invoking store_score(),
+    // then consume one use of the grant and remove it once it's used up.
+    // The payload is None when a score commitment is stored, since the score isn't known:
+    // grants restricted to a payload can't be used to store commitments.
+    // The contract owner needs no grant.
+    pub(crate) fn use_grant(&mut self, payload: Option<(u16, &str)>) {
+        let caller_id = env::signer_account_id();
+        if caller_id == self.owner_id {
+            return;
+        }
+        let mut grant = self.whitelist.get(&caller_id).unwrap_or_else(|| {
+            env::panic_str(
+                "Permission error: the account id that called this function is not whitelisted. Try with another account",
+            )
+        });
+        assert!(
+            grant.is_active(env::block_timestamp()),
+            "Permission error: your whitelist grant has expired"
+        );
+        if let Some(payload_hash) = &grant.payload_hash {
+            let (score, description) = payload.unwrap_or_else(|| {
+                env::panic_str("Permission error: your whitelist grant only allows storing a given score")
+            });
+            let payload = (score, description.to_string()).try_to_vec().unwrap();
+            assert_eq!(
+                payload_hash.0,
+                env::sha256(&payload),
+                "Permission error: your whitelist grant doesn't allow storing this score"
+            );
+        }
+
+        grant.uses -= 1;
+        if grant.uses == 0 {
+            self.remove_grant(&caller_id);
+        } else {
+            self.whitelist.insert(&caller_id, &grant);
+        }
+    }
+
+    // remove a grant from the whitelist and refund the storage it released to the contract owner
+    pub(crate) fn remove_grant(&mut self, account_id: &AccountId) -> bool {
+        let initial_storage_usage = env::storage_usage();
+        if self.whitelist.remove(account_id).is_none() {
+            return false;
+        }
+        refund_released_storage(&self.owner_id, initial_storage_usage - env::storage_usage());
+        true
+    }
+}
+
+// charge the storage used up by the call to the attached deposit, and refund the surplus
+pub(crate) fn charge_storage(storage_used: u64) {
+    let required_cost = env::storage_byte_cost() * Balance::from(storage_used);
+    let attached_deposit = env::attached_deposit();
+    assert!(
+        required_cost <= attached_deposit,
+        "Must attach {} yoctoNEAR to cover storage",
+        required_cost,
+    );
+    let refund = attached_deposit - required_cost;
+    if refund > 1 {
+        Promise::new(env::predecessor_account_id()).transfer(refund);
+    }
+}
+
+// refund the cost of the storage released by the contract to the given account
+pub(crate) fn refund_released_storage(account_id: &AccountId, storage_released: u64) {
+    let refund = env::storage_byte_cost() * Balance::from(storage_released);
+    if refund > 0 {
+        Promise::new(account_id.clone()).transfer(refund);
     }
-}
\ No newline at end of file
+}